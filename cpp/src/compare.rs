@@ -0,0 +1,70 @@
+use std::fmt;
+
+use bstr::BStr;
+use preprocessor::lexer::lex_with_options;
+use preprocessor::options::LangOptions;
+use preprocessor::token::Punct;
+use preprocessor::token::Token;
+
+// a token and the 1-based line it is on.
+pub type Located<'a> = (Token<'a>, u32);
+
+// where two token streams part: the token each has there, or `None` for one
+// that has already ended.
+pub struct Divergence<'a, 'b> {
+    pub ours: Option<Located<'a>>,
+    pub theirs: Option<Located<'b>>,
+}
+
+// `file:line: `token``, or `file: end of input`.
+pub struct Describe<'t, 'a>(pub &'t str, pub Option<Located<'a>>);
+
+// the tokens of `text`, leaving out line ends and whole directive lines, such
+// as the linemarkers `cpp -E` writes.
+pub fn reference_tokens<'a>(text: &'a BStr, options: &LangOptions) -> Vec<Located<'a>> {
+    let mut out = vec![];
+    let mut line = 1;
+    let mut at_line_start = true;
+    let mut in_directive = false;
+    for tok in lex_with_options(text, options.clone()) {
+        match tok {
+            Token::Eol => {
+                line += 1;
+                at_line_start = true;
+                in_directive = false;
+                continue;
+            }
+            Token::Eof => break,
+            Token::Punct(Punct::Hash) if at_line_start => in_directive = true,
+            _ if in_directive => {}
+            _ => out.push((tok, line)),
+        }
+        at_line_start = false;
+    }
+    out
+}
+
+pub fn first_difference<'a, 'b>(
+    ours: &[Located<'a>],
+    theirs: &[Located<'b>],
+) -> Option<Divergence<'a, 'b>> {
+    let len = ours.len().max(theirs.len());
+    (0..len)
+        .map(|i| Divergence {
+            ours: ours.get(i).copied(),
+            theirs: theirs.get(i).copied(),
+        })
+        .find(|divergence| match (divergence.ours, divergence.theirs) {
+            (Some((ours, _)), Some((theirs, _))) => ours != theirs,
+            _ => true,
+        })
+}
+
+impl fmt::Display for Describe<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            Some((tok, line)) => write!(f, "{}:{line}: `{}`", self.0, tok.spelling()),
+            None => write!(f, "{}: end of input", self.0),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use preprocessor::options::CStandard;
+use preprocessor::options::CxxStandard;
+use preprocessor::options::LangOptions;
+use preprocessor::target::Target;
+use preprocessor::warning::WarningFlag;
+use preprocessor::warning::WarningOptions;
+use serde::Deserialize;
+
+pub const FILE_NAME: &str = "rparser.toml";
+
+// per-project settings, so they needn't be repeated on every command line.
+// each key has the meaning of the flag it is named after.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub include: Vec<PathBuf>,
+    pub define: Vec<String>,
+    pub std: Option<Dialect>,
+    pub target: Option<Triple>,
+    pub freestanding: bool,
+    pub no_digraphs: bool,
+    pub warnings: Vec<WarningSetting>,
+}
+
+// a language and standard, as in `--std gnu++17`.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Dialect(pub LangOptions);
+
+// a target, as in `--target aarch64-linux-gnu`.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Triple(pub Target);
+
+// one `-W`: `NAME` enables a warning, `no-NAME` disables it and
+// `error=NAME` reports it as an error.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub enum WarningSetting {
+    Enable(WarningFlag),
+    Disable(WarningFlag),
+    Error(WarningFlag),
+}
+
+impl Config {
+    // relative include paths are taken from the directory `path` is in.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut config: Self = toml::from_str(&text).map_err(|e| e.to_string())?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for include in &mut config.include {
+            *include = dir.join(&*include);
+        }
+        Ok(config)
+    }
+}
+
+// the nearest `rparser.toml` in the current directory or one above it.
+pub fn discover() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+impl WarningSetting {
+    pub fn apply(self, warnings: &mut WarningOptions) {
+        match self {
+            Self::Enable(flag) => warnings.enable(flag),
+            Self::Disable(flag) => warnings.disable(flag),
+            Self::Error(flag) => warnings.promote(flag),
+        }
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || format!("unknown dialect `{s}`");
+        let (gnu_extensions, standard) = match (s.strip_prefix("gnu"), s.strip_prefix('c')) {
+            (Some(standard), _) => (true, standard),
+            (_, Some(standard)) => (false, standard),
+            _ => return Err(unknown()),
+        };
+        let mut options = LangOptions {
+            gnu_extensions,
+            ..LangOptions::default()
+        };
+        match standard {
+            "" => {}
+            // digraphs came with C95, though GCC's gnu89 has them
+            "89" | "90" => {
+                options.c_standard = CStandard::C89;
+                options.digraphs = gnu_extensions;
+            }
+            "99" => options.c_standard = CStandard::C99,
+            "11" => options.c_standard = CStandard::C11,
+            "17" | "18" => options.c_standard = CStandard::C17,
+            "23" => options.c_standard = CStandard::C23,
+            "++98" => options.cplusplus = Some(CxxStandard::Cxx98),
+            "++11" => options.cplusplus = Some(CxxStandard::Cxx11),
+            "++14" => options.cplusplus = Some(CxxStandard::Cxx14),
+            "++17" => options.cplusplus = Some(CxxStandard::Cxx17),
+            "++20" => options.cplusplus = Some(CxxStandard::Cxx20),
+            "++23" => options.cplusplus = Some(CxxStandard::Cxx23),
+            _ => return Err(unknown()),
+        }
+        Ok(Self(options))
+    }
+}
+
+impl TryFrom<String> for Dialect {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl FromStr for Triple {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl TryFrom<String> for Triple {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl FromStr for WarningSetting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (setting, name): (fn(WarningFlag) -> Self, _) =
+            match (s.strip_prefix("no-"), s.strip_prefix("error=")) {
+                (Some(name), _) => (Self::Disable, name),
+                (_, Some(name)) => (Self::Error, name),
+                _ => (Self::Enable, s),
+            };
+        WarningFlag::from_name(name)
+            .map(setting)
+            .ok_or_else(|| format!("unknown warning `{name}`"))
+    }
+}
+
+impl TryFrom<String> for WarningSetting {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
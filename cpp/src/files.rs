@@ -0,0 +1,41 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice;
+use bstr::ByteVec;
+use preprocessor::files::FileProvider;
+
+// headers on disk: next to the including file for `#include "..."`, then in
+// each include directory in turn.
+pub struct DiskFiles {
+    include: Vec<PathBuf>,
+}
+
+impl DiskFiles {
+    pub fn new(include: Vec<PathBuf>) -> Self {
+        Self { include }
+    }
+}
+
+impl FileProvider for DiskFiles {
+    fn resolve(&self, name: &BStr, quoted: bool, from: &BStr) -> Option<BString> {
+        let name = name.to_path().ok()?;
+        let local = quoted
+            .then(|| from.to_path().ok()?.parent())
+            .flatten()
+            .map(Path::to_owned);
+        let path = local
+            .iter()
+            .chain(&self.include)
+            .map(|dir| dir.join(name))
+            .find(|path| path.exists())?;
+        Vec::from_path_buf(path).ok().map(BString::from)
+    }
+
+    fn read(&self, path: &BStr) -> Option<Vec<u8>> {
+        fs::read(path.to_path().ok()?).ok()
+    }
+}
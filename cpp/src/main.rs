@@ -1,14 +1,510 @@
+mod compare;
+mod config;
+mod files;
+mod source;
+mod watch;
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
 use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice;
+use clap::Parser;
+use clap::ValueEnum;
+use initial::fused;
 use initial::lines::Lines;
-use preprocessor::lexer::lex;
+use preprocessor::diagnostic::Diagnostic;
+use preprocessor::diagnostic::Severity;
+use preprocessor::lexer::lex_with_spacing;
+use preprocessor::options::LangOptions;
+use preprocessor::output::TextWriter;
+use preprocessor::parser::Parser as Preprocessor;
+use preprocessor::token::Token;
+use preprocessor::warning::WarningFlag;
+use preprocessor::warning::WarningOptions;
+
+use crate::compare::Describe;
+use crate::config::Config;
+use crate::config::Dialect;
+use crate::config::Triple;
+use crate::config::WarningSetting;
+use crate::files::DiskFiles;
+use crate::source::is_stdio;
+use crate::source::Source;
+use crate::watch::watch;
+
+#[derive(Parser)]
+struct Args {
+    /// The files to preprocess, each as its own translation unit; `-` is
+    /// stdin.
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Write the output here instead of to stdout, which `-` also names. With
+    /// several inputs this is a directory, and each output is named after its
+    /// input.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Add a directory to search for headers.
+    #[arg(short = 'I', value_name = "DIR")]
+    include: Vec<PathBuf>,
+    /// Define NAME as VALUE, or as 1, before reading the input.
+    #[arg(short = 'D', value_name = "NAME[=VALUE]")]
+    define: Vec<String>,
+    /// The language and standard: c89 to c23, c++98 to c++23, or either with
+    /// `gnu` in place of `c` to accept GNU extensions. c alone is c23.
+    #[arg(long, value_name = "DIALECT")]
+    std: Option<Dialect>,
+    /// The target triple, such as x86_64-linux-gnu or arm-none-eabi, which
+    /// decides the predefined macros and the sizes of types. A triple
+    /// without a known operating system is freestanding. x86_64-linux-gnu
+    /// by default.
+    #[arg(long, value_name = "TRIPLE")]
+    target: Option<Triple>,
+    /// Don't recognize the digraphs `<:`, `:>`, `<%`, `%>`, `%:` and `%:%:`,
+    /// as with --std c89.
+    #[arg(long)]
+    no_digraphs: bool,
+    /// Preprocess for a freestanding environment, whatever the target.
+    #[arg(long)]
+    freestanding: bool,
+    /// Enable a warning, disable it with `no-NAME`, or report it as an error
+    /// with `error=NAME`.
+    #[arg(short = 'W', value_name = "WARNING")]
+    warnings: Vec<WarningSetting>,
+    /// Read settings from this file instead of the nearest rparser.toml.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Don't read settings from any rparser.toml.
+    #[arg(long, conflicts_with = "config")]
+    no_config: bool,
+    /// Memory-map source files instead of reading them into memory. Ignored
+    /// with --watch, which expects the files to change.
+    #[arg(long)]
+    mmap: bool,
+    /// Splice lines and remove comments in one pass.
+    #[arg(long)]
+    single_pass: bool,
+    /// Print preprocessing statistics to stderr.
+    #[arg(long)]
+    stats: bool,
+    /// Warn where the input exceeds the standard's minimum translation limits.
+    #[arg(long)]
+    check_limits: bool,
+    /// Warn about extensions the standard doesn't allow and macros that expand to `defined` in #if; implies --check-limits.
+    #[arg(long)]
+    pedantic: bool,
+    /// Like --pedantic, but report the extensions as errors.
+    #[arg(long)]
+    pedantic_errors: bool,
+    /// Print each header an input includes to stderr, after a dot for each
+    /// level of nesting.
+    #[arg(short = 'H')]
+    trace_includes: bool,
+    /// Preprocess again whenever an input or a header it includes changes.
+    #[arg(long)]
+    watch: bool,
+    /// Compare the preprocessed tokens with a reference output, such as that
+    /// of `cpp -E`, and report where they first differ.
+    #[arg(long, value_name = "FILE")]
+    check_against: Option<PathBuf>,
+    /// Print the token stream or an analysis of the input instead of the
+    /// preprocessed source.
+    #[arg(long, value_enum)]
+    emit: Option<Emit>,
+    /// The format of --emit=include-graph.
+    #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+    graph_format: GraphFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Emit {
+    Tokens,
+    IncludeGraph,
+    CondReport,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Json,
+}
+
+impl Args {
+    // settings from a config file; those on the command line win.
+    fn merge(&mut self, config: Config) {
+        self.include.extend(config.include);
+        self.define.splice(0..0, config.define);
+        self.warnings.splice(0..0, config.warnings);
+        self.std = self.std.take().or(config.std);
+        self.target = self.target.take().or(config.target);
+        self.freestanding |= config.freestanding;
+        self.no_digraphs |= config.no_digraphs;
+    }
+
+    fn lang_options(&self) -> LangOptions {
+        let mut options = self
+            .std
+            .clone()
+            .map_or_else(LangOptions::default, |dialect| dialect.0);
+        if let Some(Triple(target)) = &self.target {
+            options.target = *target;
+        }
+        if self.freestanding {
+            options.target.hosted = false;
+        }
+        if self.no_digraphs {
+            options.digraphs = false;
+        }
+        options
+    }
+
+    fn warning_options(&self) -> WarningOptions {
+        let mut warnings = WarningOptions::default();
+        for setting in &self.warnings {
+            setting.apply(&mut warnings);
+        }
+        if self.check_limits || self.pedantic || self.pedantic_errors {
+            warnings.enable(WarningFlag::ImplementationLimits);
+        }
+        if self.pedantic || self.pedantic_errors {
+            warnings.enable(WarningFlag::ExpansionToDefined);
+        }
+        if self.pedantic_errors {
+            warnings.promote(WarningFlag::Pedantic);
+        } else if self.pedantic {
+            warnings.enable(WarningFlag::Pedantic);
+        }
+        warnings
+    }
+
+    // a parser for `src`, with the macros from `-D` defined.
+    fn parser<'a>(
+        &'a self,
+        src: &'a BStr,
+        options: &LangOptions,
+    ) -> Preprocessor<'a, impl Iterator<Item = (Token<'a>, bool)>> {
+        let tokens = lex_with_spacing(src, options.clone());
+        let files = DiskFiles::new(self.include.clone());
+        let mut parser = Preprocessor::new(tokens, options.clone())
+            .with_text(src)
+            .with_file_provider(Box::new(files));
+        for define in &self.define {
+            let (name, body) = define.split_once('=').unwrap_or((define, "1"));
+            parser = parser.with_define(name, body.into());
+        }
+        parser
+    }
+
+    // where the output for `input` goes; `None` is stdout.
+    fn output_path(&self, input: &Path) -> Option<PathBuf> {
+        match &self.output {
+            Some(output) if is_stdio(output) => return None,
+            output if self.inputs.len() == 1 => return output.clone(),
+            _ => {}
+        }
+        let name = if is_stdio(input) {
+            PathBuf::from("stdin.i")
+        } else {
+            input.with_extension("i")
+        };
+        let name = name.file_name().unwrap_or(name.as_os_str());
+        match &self.output {
+            Some(dir) => Some(dir.join(name)),
+            None => Some(input.with_file_name(name)),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = Args::parse();
+    let config = match &args.config {
+        Some(path) => Some(path.clone()),
+        None if args.no_config => None,
+        None => config::discover(),
+    };
+    if let Some(path) = config {
+        match Config::load(&path) {
+            Ok(config) => args.merge(config),
+            Err(e) => {
+                eprintln!("{}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(dir) = args
+        .output
+        .as_ref()
+        .filter(|dir| args.inputs.len() > 1 && !is_stdio(dir))
+    {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("{}: {e}", dir.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    if args.check_against.is_some() && args.inputs.len() > 1 {
+        eprintln!("--check-against takes a single input");
+        return ExitCode::FAILURE;
+    }
+    if args.watch && args.inputs.iter().any(|input| is_stdio(input)) {
+        eprintln!("stdin can't be watched");
+        return ExitCode::FAILURE;
+    }
+    let mut watched = vec![];
+    let status = run(&args, &mut watched);
+    if !args.watch {
+        return status;
+    }
+    watch(watched, || {
+        let mut watched = vec![];
+        run(&args, &mut watched);
+        watched
+    })
+}
+
+// preprocess every input, adding the files each depends on to `watched`.
+fn run(args: &Args, watched: &mut Vec<PathBuf>) -> ExitCode {
+    let mut status = ExitCode::SUCCESS;
+    for input in &args.inputs {
+        // a mapped file that shrinks under us would fault on the next read
+        let contents = match Source::load(input, args.mmap && !args.watch) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("{}: {e}", input.display());
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        let src = BStr::new(&*contents);
+        if args.watch {
+            watched.push(input.clone());
+            watched.extend(headers(args, input, src));
+        }
+        if args.trace_includes {
+            trace_includes(args, input, src);
+        }
+        if let Some(reference) = &args.check_against {
+            match check_against(args, input, src, reference) {
+                Ok(true) => {}
+                Ok(false) => status = ExitCode::FAILURE,
+                Err(e) => {
+                    eprintln!("{}: {e}", reference.display());
+                    status = ExitCode::FAILURE;
+                }
+            }
+            continue;
+        }
+        let result = match args.output_path(input) {
+            Some(path) if path == *input => Err(format!(
+                "{}: the output would overwrite the input",
+                input.display()
+            )),
+            Some(path) => File::create(&path)
+                .and_then(|file| preprocess(args, input, src, &mut BufWriter::new(file)))
+                .map_err(|e| format!("{}: {e}", path.display())),
+            None => match preprocess(args, input, src, &mut io::stdout().lock()) {
+                // the reader has gone away, as with `| head`
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return status,
+                result => result.map_err(|e| format!("{}: {e}", input.display())),
+            },
+        };
+        match result {
+            Ok(true) => {}
+            Ok(false) => status = ExitCode::FAILURE,
+            Err(e) => {
+                eprintln!("{e}");
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+// the headers `src` includes, in order, each with its path if it can be
+// found: next to `file` for `#include "..."`, or in an include directory.
+fn includes(args: &Args, file: &Path, src: &BStr) -> Vec<(BString, Option<PathBuf>)> {
+    let src = fused::process(src);
+    let mut parser = args
+        .parser(src.as_ref(), &args.lang_options())
+        .with_file_name(display_name(file));
+    parser.by_ref().for_each(drop);
+    let graph = parser.include_graph();
+    graph
+        .edges
+        .iter()
+        .map(|edge| {
+            let path = edge.resolved.then(|| graph.files()[edge.to].to_path());
+            let path = path.and_then(Result::ok).map(Path::to_owned);
+            (edge.spelling.clone(), path)
+        })
+        .collect()
+}
 
-fn main() {
-    let contents = std::fs::read("main.c").unwrap();
-    let src = Lines::new(BStr::new(&contents))
-        .merge_escaped_newlines()
-        .delete_comments()
-        .finish();
-    for token in lex(src.as_ref()) {
-        println!("{token}");
+// every header `input` includes that can be found, directly or through
+// other headers.
+fn headers(args: &Args, input: &Path, src: &BStr) -> Vec<PathBuf> {
+    let mut seen = vec![];
+    walk_includes(args, input, src, 1, &mut seen, &mut |_, _, _| {});
+    seen
+}
+
+// print the headers `src` includes, and those they include in turn, after a
+// dot for each level of nesting, as `-H` does.
+fn trace_includes(args: &Args, file: &Path, src: &BStr) {
+    walk_includes(
+        args,
+        file,
+        src,
+        1,
+        &mut vec![],
+        &mut |depth, spelling, path| {
+            let dots = ".".repeat(depth);
+            match path {
+                Some(path) => eprintln!("{dots} {}", path.display()),
+                None => eprintln!("{dots} {spelling} (not found)"),
+            }
+        },
+    );
+}
+
+// call `visit` with the depth, spelling and path of each header `src`
+// includes, then walk that header. the parser doesn't read headers, so this
+// follows them itself, each on its own: macros don't carry over, and a header
+// is only followed, and visited, the first time, as though every header had
+// an include guard. the headers followed are added to `seen`.
+fn walk_includes(
+    args: &Args,
+    file: &Path,
+    src: &BStr,
+    depth: usize,
+    seen: &mut Vec<PathBuf>,
+    visit: &mut dyn FnMut(usize, &BStr, Option<&Path>),
+) {
+    for (spelling, path) in includes(args, file, src) {
+        let Some(path) = path else {
+            visit(depth, spelling.as_ref(), None);
+            continue;
+        };
+        if seen.contains(&path) {
+            continue;
+        }
+        seen.push(path.clone());
+        visit(depth, spelling.as_ref(), Some(&path));
+        match fs::read(&path) {
+            Ok(text) => walk_includes(args, &path, text.as_bstr(), depth + 1, seen, visit),
+            Err(e) => eprintln!("{}: {e}", path.display()),
+        }
+    }
+}
+
+// print where the preprocessed `src` and the tokens of `reference` first
+// differ; false if they do, or if preprocessing reported an error.
+fn check_against(args: &Args, input: &Path, src: &BStr, reference: &Path) -> io::Result<bool> {
+    let file_name = display_name(input);
+    let reference_name = reference.display().to_string();
+    let reference = fused::process(fs::read(reference)?.as_bstr());
+    let src = phases(args, src);
+    let options = args.lang_options();
+    let mut ours = vec![];
+    let mut line = 1;
+    let mut clean = true;
+    for result in args.parser(src.as_ref(), &options) {
+        match result {
+            Ok(Token::Eol) => line += 1,
+            Ok(Token::Eof) => {}
+            Ok(tok) => ours.push((tok, line)),
+            Err(e) => {
+                eprintln!("{file_name}: {}", Diagnostic::from(&e));
+                clean = false;
+            }
+        }
+    }
+    let theirs = compare::reference_tokens(reference.as_ref(), &options);
+    let Some(divergence) = compare::first_difference(&ours, &theirs) else {
+        return Ok(clean);
+    };
+    println!("first difference:");
+    println!("  {}", Describe(&file_name, divergence.ours));
+    println!("  {}", Describe(&reference_name, divergence.theirs));
+    Ok(false)
+}
+
+fn display_name(input: &Path) -> String {
+    if is_stdio(input) {
+        "<stdin>".to_owned()
+    } else {
+        input.display().to_string()
+    }
+}
+
+// line splicing and comment removal. lines that were joined come back as
+// empty lines, so the parser counts physical lines.
+fn phases(args: &Args, src: &BStr) -> BString {
+    if args.single_pass {
+        fused::process_preserving_lines(src)
+    } else {
+        Lines::new(src)
+            .merge_escaped_newlines()
+            .delete_comments()
+            .preserve_line_numbers()
+            .finish()
+    }
+}
+
+// preprocess one translation unit into `out`. diagnostics go to stderr,
+// prefixed with the file they are about. false if any was an error.
+fn preprocess(args: &Args, input: &Path, src: &BStr, out: &mut dyn Write) -> io::Result<bool> {
+    let file_name = display_name(input);
+    let src = phases(args, src);
+    let options = args.lang_options();
+    let warnings = args.warning_options();
+    let mut clean = true;
+    let mut text = TextWriter::new(options.clone());
+    let mut parser = args
+        .parser(src.as_ref(), &options)
+        .with_file_name(file_name.as_str())
+        .with_warning_options(warnings);
+    while let Some(result) = parser.next() {
+        match result {
+            Ok(token) => match args.emit {
+                None => text.write(token, parser.space_before()),
+                Some(Emit::Tokens) => writeln!(out, "{token}")?,
+                Some(_) => {}
+            },
+            Err(e) => {
+                eprintln!("{file_name}: {}", Diagnostic::from(&e));
+                clean = false;
+            }
+        }
+        for warning in parser.take_warnings() {
+            eprintln!("{file_name}: {warning}");
+            clean &= warning.severity != Severity::Error;
+        }
+    }
+    match args.emit {
+        None => out.write_all(&text.finish())?,
+        Some(Emit::IncludeGraph) => {
+            let graph = parser.full_include_graph();
+            match args.graph_format {
+                GraphFormat::Dot => write!(out, "{}", graph.to_dot())?,
+                GraphFormat::Json => writeln!(out, "{}", graph.to_json())?,
+            }
+        }
+        Some(Emit::CondReport) => write!(out, "{}", parser.coverage())?,
+        Some(Emit::Tokens) => {}
+    }
+    if args.stats {
+        if args.inputs.len() > 1 {
+            eprintln!("{file_name}:");
+        }
+        eprint!("{}", parser.stats());
     }
+    out.flush()?;
+    Ok(clean)
 }
@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+pub enum Source {
+    Read(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Source {
+    // with `mmap`, map the file instead of copying it; anything that can't
+    // be mapped (empty files, pipes, some network filesystems) is read
+    // normally instead. `-` is stdin.
+    pub fn load(path: &Path, mmap: bool) -> io::Result<Self> {
+        if is_stdio(path) {
+            let mut bytes = vec![];
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            return Ok(Self::Read(bytes));
+        }
+        if mmap {
+            // SAFETY: the map is only ever read, and we don't modify the file
+            // while it is alive. nor may anyone else, so callers must not map
+            // files they expect to change, as `--watch` does.
+            let mapped = File::open(path).and_then(|file| unsafe { Mmap::map(&file) });
+            if let Ok(map) = mapped {
+                return Ok(Self::Mapped(map));
+            }
+        }
+        std::fs::read(path).map(Self::Read)
+    }
+}
+
+// `-`, which stands for stdin or stdout.
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+impl Deref for Source {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Read(bytes) => bytes,
+            Self::Mapped(map) => map,
+        }
+    }
+}
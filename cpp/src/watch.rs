@@ -0,0 +1,31 @@
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// poll `files` and call `rebuild` whenever one of them changes; `rebuild`
+// returns the files to watch from then on. runs until the process is killed.
+pub fn watch(mut files: Vec<PathBuf>, mut rebuild: impl FnMut() -> Vec<PathBuf>) -> ! {
+    let mut stamps = modified(&files);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let now = modified(&files);
+        let Some(changed) = (0..files.len()).find(|&i| now[i] != stamps[i]) else {
+            continue;
+        };
+        eprintln!("{}: changed, preprocessing again", files[changed].display());
+        files = rebuild();
+        stamps = modified(&files);
+    }
+}
+
+// a file that can't be read has no time, so its reappearance is a change.
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(file).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
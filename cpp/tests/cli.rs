@@ -0,0 +1,82 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+
+// the output of preprocessing `src` from stdin with `args`, run in `dir`.
+fn cpp(dir: &Path, args: &[&str], src: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cpp"))
+        .current_dir(dir)
+        .args(args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(src.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// a fresh directory for a test to run in.
+fn scratch(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("cpp-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn digraphs_can_be_turned_off() {
+    let dir = scratch("digraphs");
+    let tokens = ["--emit", "tokens", "--no-config"];
+    let src = "<:\n";
+    let output = |args: &[&str]| cpp(&dir, &[&tokens[..], args].concat(), src);
+    assert!(output(&[]).contains("{punct .l_brack}"));
+    assert!(output(&["--no-digraphs"]).contains("{punct .lt}"));
+    assert!(output(&["--std", "c89"]).contains("{punct .lt}"));
+    assert!(output(&["--std", "gnu89"]).contains("{punct .l_brack}"));
+
+    fs::write(dir.join("rparser.toml"), "no_digraphs = true\n").unwrap();
+    let configured = cpp(&dir, &["--emit", "tokens"], src);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(configured.contains("{punct .lt}"), "{configured}");
+}
+
+#[test]
+fn settings_come_from_the_nearest_config() {
+    let dir = scratch("config");
+    fs::create_dir_all(dir.join("inc")).unwrap();
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("inc/x.h"), "").unwrap();
+    let config = "include = [\"inc\"]\ndefine = [\"A=2\"]\nstd = \"c++20\"\n";
+    fs::write(dir.join("rparser.toml"), config).unwrap();
+    let sub = dir.join("sub");
+
+    let src = "#if A == 2 && __cplusplus == 202002L\nyes\n#endif\n";
+    assert!(cpp(&sub, &[], src).contains("yes"));
+    // the command line wins
+    assert!(!cpp(&sub, &["--std", "c++17"], src).contains("yes"));
+    assert!(!cpp(&sub, &["--no-config"], src).contains("yes"));
+    // include paths are relative to the config file
+    let graph = cpp(&sub, &["--emit", "include-graph"], "#include <x.h>\n");
+    let header = dir.join("inc").join("x.h");
+    assert!(graph.contains(&*header.to_string_lossy()), "{graph}");
+
+    fs::write(dir.join("rparser.toml"), "unknown = 1\n").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_cpp"))
+        .current_dir(&sub)
+        .arg("-")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!status.success());
+}
@@ -0,0 +1,45 @@
+use std::fs;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// a header included through another one is watched as well.
+#[test]
+fn nested_headers_are_watched() {
+    let dir = std::env::temp_dir().join(format!("cpp-watch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.c"), "#include \"a.h\"\n").unwrap();
+    fs::write(dir.join("a.h"), "#include \"b.h\"\n").unwrap();
+    fs::write(dir.join("b.h"), "int b;\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cpp"))
+        .arg("--watch")
+        .arg("-o")
+        .arg(dir.join("main.i"))
+        .arg(dir.join("main.c"))
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stderr = BufReader::new(child.stderr.take().unwrap());
+    let (lines, received) = mpsc::channel();
+    thread::spawn(move || {
+        for line in stderr.lines() {
+            if lines.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+
+    // give the first run time to finish before changing the header
+    thread::sleep(Duration::from_millis(500));
+    fs::write(dir.join("b.h"), "int b2;\n").unwrap();
+    let line = received.recv_timeout(Duration::from_secs(5));
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(line.unwrap().contains("b.h: changed"));
+}
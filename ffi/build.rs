@@ -0,0 +1,16 @@
+use std::env;
+use std::path::PathBuf;
+
+// generate the C header into `OUT_DIR`. `tests/header.rs` checks that the
+// copy in `include/` matches it.
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("rparser.h");
+    let config = cbindgen::Config::from_file(PathBuf::from(&crate_dir).join("cbindgen.toml"))
+        .expect("cbindgen.toml should be valid");
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("the C interface should be expressible in C")
+        .write_to_file(out);
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,98 @@
+/* C interface to the rparser preprocessor. Generated from ffi/src/lib.rs by cbindgen; don't edit. */
+
+#ifndef RPARSER_H
+#define RPARSER_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+typedef enum RparserTokenKind {
+  RPARSER_TOKEN_KIND_IDENT,
+  RPARSER_TOKEN_KIND_STRING_LIT,
+  RPARSER_TOKEN_KIND_NUMBER,
+  RPARSER_TOKEN_KIND_PUNCT,
+  RPARSER_TOKEN_KIND_MODULE_KEYWORD,
+  RPARSER_TOKEN_KIND_KEYWORD,
+  RPARSER_TOKEN_KIND_OTHER,
+  RPARSER_TOKEN_KIND_EOL,
+  RPARSER_TOKEN_KIND_EOF,
+} RparserTokenKind;
+
+typedef struct Rparser Rparser;
+
+// `text` points into the handle and stays valid until the next
+// `rparser_feed` or `rparser_free`. It is not NUL-terminated.
+typedef struct RparserToken {
+  enum RparserTokenKind kind;
+  const uint8_t *text;
+  size_t len;
+} RparserToken;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Create a handle that preprocesses C with default options.
+struct Rparser *rparser_new(void);
+
+// Free a handle.
+//
+// # Safety
+//
+// `handle` must come from `rparser_new` and not be used afterwards. Null is
+// ignored.
+void rparser_free(struct Rparser *handle);
+
+// Enable or disable GNU extensions.
+//
+// # Safety
+//
+// `handle` must be a live handle.
+void rparser_set_gnu_extensions(struct Rparser *handle, bool enabled);
+
+// Preprocess C++ of the given `__cplusplus` value, or C if it is 0. Returns
+// false for an unknown value.
+//
+// # Safety
+//
+// `handle` must be a live handle.
+bool rparser_set_cplusplus(struct Rparser *handle, int64_t version);
+
+// Append `len` bytes of source text. Tokens already returned become
+// invalid and iteration starts again from the beginning.
+//
+// # Safety
+//
+// `handle` must be a live handle and `data` must point to `len` readable
+// bytes.
+void rparser_feed(struct Rparser *handle, const uint8_t *data, size_t len);
+
+// Store the next token in `token`. Returns false once every token has been
+// returned.
+//
+// # Safety
+//
+// `handle` must be a live handle and `token` must be writable.
+bool rparser_next_token(struct Rparser *handle, struct RparserToken *token);
+
+// The number of diagnostics for the input fed so far.
+//
+// # Safety
+//
+// `handle` must be a live handle.
+size_t rparser_diagnostic_count(struct Rparser *handle);
+
+// The diagnostic at `index` as a NUL-terminated string, or null if there is
+// none. Valid until the next `rparser_feed` or `rparser_free`.
+//
+// # Safety
+//
+// `handle` must be a live handle.
+const char *rparser_diagnostic(struct Rparser *handle, size_t index);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RPARSER_H */
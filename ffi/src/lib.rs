@@ -0,0 +1,233 @@
+use std::ffi::c_char;
+use std::ffi::CString;
+use std::ops::Range;
+use std::ptr;
+use std::slice;
+
+use bstr::BStr;
+use preprocessor::diagnostic::Diagnostic;
+use preprocessor::lexer::lex_with_options;
+use preprocessor::options::CxxStandard;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::Parser;
+use preprocessor::token::Token;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RparserTokenKind {
+    Ident,
+    StringLit,
+    Number,
+    Punct,
+    ModuleKeyword,
+    Keyword,
+    Other,
+    Eol,
+    Eof,
+}
+
+/// `text` points into the handle and stays valid until the next
+/// `rparser_feed` or `rparser_free`. It is not NUL-terminated.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RparserToken {
+    pub kind: RparserTokenKind,
+    pub text: *const u8,
+    pub len: usize,
+}
+
+// the opaque handle C code holds.
+#[derive(Default)]
+pub struct Rparser {
+    options: LangOptions,
+    input: Vec<u8>,
+    // set once the buffered input has been preprocessed
+    output: Option<Output>,
+}
+
+#[derive(Default)]
+struct Output {
+    spellings: Vec<u8>,
+    tokens: Vec<(RparserTokenKind, Range<usize>)>,
+    diagnostics: Vec<CString>,
+    pos: usize,
+}
+
+impl Rparser {
+    fn output(&mut self) -> &mut Output {
+        let Self {
+            options,
+            input,
+            output,
+        } = self;
+        output.get_or_insert_with(|| preprocess(BStr::new(input), options))
+    }
+}
+
+fn preprocess(input: &BStr, options: &LangOptions) -> Output {
+    let text = initial::fused::process(input);
+    let mut parser = Parser::new(
+        lex_with_options(text.as_ref(), options.clone()),
+        options.clone(),
+    )
+    .with_text(text.as_ref());
+    let mut output = Output::default();
+    while let Some(result) = parser.next() {
+        match result {
+            Ok(tok) => {
+                let start = output.spellings.len();
+                output.spellings.extend_from_slice(tok.spelling());
+                output
+                    .tokens
+                    .push((kind(tok), start..output.spellings.len()));
+                if let Token::Eof = tok {
+                    break;
+                }
+            }
+            Err(e) => output
+                .diagnostics
+                .push(diagnostic(Diagnostic::from(&e).to_string())),
+        }
+        for warning in parser.take_warnings() {
+            output.diagnostics.push(diagnostic(warning.to_string()));
+        }
+    }
+    output
+}
+
+fn kind(tok: Token<'_>) -> RparserTokenKind {
+    match tok {
+        Token::Ident(_) => RparserTokenKind::Ident,
+        Token::StringLit(_) => RparserTokenKind::StringLit,
+        Token::Number(_) => RparserTokenKind::Number,
+        Token::Punct(_) => RparserTokenKind::Punct,
+        Token::ModuleKeyword(_) => RparserTokenKind::ModuleKeyword,
+        Token::Keyword(_) => RparserTokenKind::Keyword,
+        Token::Other(_) => RparserTokenKind::Other,
+        Token::Eol => RparserTokenKind::Eol,
+        Token::Eof => RparserTokenKind::Eof,
+    }
+}
+
+// messages can quote source text, which may contain NULs.
+fn diagnostic(message: String) -> CString {
+    CString::new(message.replace('\0', "\\0")).unwrap_or_default()
+}
+
+/// Create a handle that preprocesses C with default options.
+#[no_mangle]
+pub extern "C" fn rparser_new() -> *mut Rparser {
+    Box::into_raw(Box::default())
+}
+
+/// Free a handle.
+///
+/// # Safety
+///
+/// `handle` must come from `rparser_new` and not be used afterwards. Null is
+/// ignored.
+#[no_mangle]
+pub unsafe extern "C" fn rparser_free(handle: *mut Rparser) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Enable or disable GNU extensions.
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn rparser_set_gnu_extensions(handle: *mut Rparser, enabled: bool) {
+    let handle = &mut *handle;
+    handle.options.gnu_extensions = enabled;
+    handle.output = None;
+}
+
+/// Preprocess C++ of the given `__cplusplus` value, or C if it is 0. Returns
+/// false for an unknown value.
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn rparser_set_cplusplus(handle: *mut Rparser, version: i64) -> bool {
+    let handle = &mut *handle;
+    let std = match version {
+        0 => None,
+        _ => match CxxStandard::from_version(version) {
+            Some(std) => Some(std),
+            None => return false,
+        },
+    };
+    handle.options.cplusplus = std;
+    handle.output = None;
+    true
+}
+
+/// Append `len` bytes of source text. Tokens already returned become
+/// invalid and iteration starts again from the beginning.
+///
+/// # Safety
+///
+/// `handle` must be a live handle and `data` must point to `len` readable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rparser_feed(handle: *mut Rparser, data: *const u8, len: usize) {
+    let handle = &mut *handle;
+    if len > 0 {
+        handle
+            .input
+            .extend_from_slice(slice::from_raw_parts(data, len));
+    }
+    handle.output = None;
+}
+
+/// Store the next token in `token`. Returns false once every token has been
+/// returned.
+///
+/// # Safety
+///
+/// `handle` must be a live handle and `token` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn rparser_next_token(
+    handle: *mut Rparser,
+    token: *mut RparserToken,
+) -> bool {
+    let output = (*handle).output();
+    let Some((kind, range)) = output.tokens.get(output.pos).cloned() else {
+        return false;
+    };
+    output.pos += 1;
+    *token = RparserToken {
+        kind,
+        text: output.spellings[range.clone()].as_ptr(),
+        len: range.len(),
+    };
+    true
+}
+
+/// The number of diagnostics for the input fed so far.
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn rparser_diagnostic_count(handle: *mut Rparser) -> usize {
+    (*handle).output().diagnostics.len()
+}
+
+/// The diagnostic at `index` as a NUL-terminated string, or null if there is
+/// none. Valid until the next `rparser_feed` or `rparser_free`.
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn rparser_diagnostic(handle: *mut Rparser, index: usize) -> *const c_char {
+    match (*handle).output().diagnostics.get(index) {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
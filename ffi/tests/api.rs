@@ -0,0 +1,78 @@
+use std::ffi::CStr;
+use std::slice;
+
+use rparser::rparser_diagnostic;
+use rparser::rparser_diagnostic_count;
+use rparser::rparser_feed;
+use rparser::rparser_free;
+use rparser::rparser_new;
+use rparser::rparser_next_token;
+use rparser::rparser_set_cplusplus;
+use rparser::Rparser;
+use rparser::RparserToken;
+use rparser::RparserTokenKind;
+
+// the spellings of the tokens `handle` hands out, up to `Eof`.
+unsafe fn tokens(handle: *mut Rparser) -> Vec<(RparserTokenKind, String)> {
+    let mut token = RparserToken {
+        kind: RparserTokenKind::Eof,
+        text: std::ptr::null(),
+        len: 0,
+    };
+    let mut tokens = vec![];
+    while rparser_next_token(handle, &mut token) {
+        let text = slice::from_raw_parts(token.text, token.len);
+        tokens.push((token.kind, String::from_utf8_lossy(text).into_owned()));
+    }
+    tokens
+}
+
+#[test]
+fn feed_and_read_back() {
+    unsafe {
+        let handle = rparser_new();
+        let src = b"#if 1\na + 1\n#endif\n";
+        rparser_feed(handle, src.as_ptr(), src.len());
+        let kinds: Vec<_> = tokens(handle)
+            .into_iter()
+            .filter(|(kind, _)| *kind != RparserTokenKind::Eol)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (RparserTokenKind::Ident, "a".to_owned()),
+                (RparserTokenKind::Punct, "+".to_owned()),
+                (RparserTokenKind::Number, "1".to_owned()),
+                (RparserTokenKind::Eof, String::new()),
+            ]
+        );
+        // feeding more starts again from the beginning
+        let more = b"b\n";
+        rparser_feed(handle, more.as_ptr(), more.len());
+        assert_eq!(
+            tokens(handle)
+                .iter()
+                .filter(|(kind, _)| *kind == RparserTokenKind::Ident)
+                .count(),
+            2
+        );
+        rparser_free(handle);
+    }
+}
+
+#[test]
+fn diagnostics_and_options() {
+    unsafe {
+        let handle = rparser_new();
+        assert!(!rparser_set_cplusplus(handle, 199000));
+        assert!(rparser_set_cplusplus(handle, 202002));
+        let src = b"#if 1 / 0\n#endif\n";
+        rparser_feed(handle, src.as_ptr(), src.len());
+        assert_eq!(rparser_diagnostic_count(handle), 1);
+        let message = CStr::from_ptr(rparser_diagnostic(handle, 0));
+        assert!(message.to_str().unwrap().contains("PP0012"), "{message:?}");
+        assert!(rparser_diagnostic(handle, 1).is_null());
+        rparser_free(handle);
+        rparser_free(std::ptr::null_mut());
+    }
+}
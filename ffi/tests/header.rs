@@ -0,0 +1,21 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// the checked-in header must be the one cbindgen generates from the source.
+// run with `BLESS=1` to update it.
+#[test]
+fn header_is_up_to_date() {
+    let generated = fs::read_to_string(concat!(env!("OUT_DIR"), "/rparser.h")).unwrap();
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("include/rparser.h");
+    if env::var_os("BLESS").is_some() {
+        fs::write(&path, &generated).unwrap();
+        return;
+    }
+    let checked_in = fs::read_to_string(&path).unwrap();
+    assert!(
+        checked_in == generated,
+        "{} is out of date; run `BLESS=1 cargo test -p ffi --test header`",
+        path.display()
+    );
+}
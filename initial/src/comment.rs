@@ -0,0 +1,105 @@
+pub(crate) struct CommentState {
+    pub(crate) in_string: bool,
+    pub(crate) in_char: bool,
+    pub(crate) in_block_comment: bool,
+    pub(crate) in_line_comment: bool,
+    pub(crate) prev_char: u8,
+    // the last character was a backslash inside a literal
+    escaped: bool,
+    // inside a pp-number, where `'` is a digit separator
+    in_number: bool,
+}
+
+pub(crate) struct Emit {
+    pub(crate) ch: u8,
+    pub(crate) pop_count: usize,
+}
+
+impl CommentState {
+    pub(crate) fn new() -> Self {
+        Self {
+            in_string: false,
+            in_char: false,
+            in_block_comment: false,
+            in_line_comment: false,
+            prev_char: 0,
+            escaped: false,
+            in_number: false,
+        }
+    }
+}
+
+impl Emit {
+    pub(crate) fn new(ch: u8) -> Self {
+        Self { ch, pop_count: 0 }
+    }
+}
+
+pub(crate) fn should_emit(ch: u8, comments: &mut CommentState) -> Option<Emit> {
+    if comments.in_string || comments.in_char {
+        let quote = if comments.in_string { b'"' } else { b'\'' };
+        if comments.escaped {
+            comments.escaped = false;
+        } else if ch == b'\\' {
+            comments.escaped = true;
+        } else if ch == quote || ch == b'\n' {
+            // an unterminated literal ends with its line
+            comments.in_string = false;
+            comments.in_char = false;
+        }
+        Some(Emit::new(ch))
+    } else if comments.in_block_comment && ch == b'/' && comments.prev_char == b'*' {
+        comments.in_block_comment = false;
+        None
+    } else if comments.in_line_comment && ch == b'\n' {
+        comments.in_line_comment = false;
+        Some(Emit::new(ch))
+    } else if comments.in_line_comment || comments.in_block_comment {
+        None
+    } else {
+        let prev = comments.prev_char;
+        comments.in_number = match ch {
+            b'0'..=b'9' => comments.in_number || prev == b'.' || !is_word_char(prev),
+            b'\'' | b'.' => comments.in_number,
+            b'+' | b'-' => comments.in_number && matches!(prev, b'e' | b'E' | b'p' | b'P'),
+            _ => comments.in_number && is_word_char(ch),
+        };
+        match ch {
+            b'/' => {
+                if comments.prev_char == b'/' {
+                    comments.in_line_comment = true;
+                    Some(Emit {
+                        ch: b' ',
+                        pop_count: 2,
+                    })
+                } else {
+                    Some(Emit::new(ch))
+                }
+            }
+            b'*' => {
+                if comments.prev_char == b'/' {
+                    comments.in_block_comment = true;
+                    Some(Emit {
+                        ch: b' ',
+                        pop_count: 2,
+                    })
+                } else {
+                    Some(Emit::new(ch))
+                }
+            }
+            b'"' => {
+                comments.in_string = true;
+                Some(Emit::new(ch))
+            }
+            b'\'' if !comments.in_number => {
+                comments.in_char = true;
+                Some(Emit::new(ch))
+            }
+            _ => Some(Emit::new(ch)),
+        }
+    }
+}
+
+fn is_word_char(ch: u8) -> bool {
+    ch.is_ascii_alphanumeric() || ch == b'_'
+}
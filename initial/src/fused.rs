@@ -0,0 +1,275 @@
+use std::ops::Range;
+
+use bstr::BStr;
+use bstr::BString;
+
+use crate::comment::should_emit;
+use crate::comment::CommentState;
+use crate::comment::Emit;
+
+// phases 1-3 in a single pass, producing the same text as
+// `Lines::new(input).merge_escaped_newlines().delete_comments().finish()`
+// without building the intermediate lines. there is no source map or
+// pedantic mode on this path.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub fn process(input: &BStr) -> BString {
+    let mut pass = Fused::new(input);
+    while pass.line() {}
+    pass.finish()
+}
+
+// `process`, with an empty line after each logical line for every physical
+// line joined onto it, as `Lines::preserve_line_numbers` does.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub fn process_preserving_lines(input: &BStr) -> BString {
+    let mut pass = Fused::new(input);
+    pass.preserve_line_numbers = true;
+    while pass.line() {}
+    pass.finish()
+}
+
+// the offsets just past each line break where no comment, literal or splice
+// carries over into the next line. processing the pieces between them
+// separately gives the same text as processing the whole input.
+pub fn split_points(input: &BStr) -> impl Iterator<Item = usize> + '_ {
+    let mut pass = Fused::new(input);
+    std::iter::from_fn(move || {
+        while pass.line() {
+            if pass.is_clean() && !in_raw_string(&pass.text) {
+                // only the state matters here
+                pass.text.clear();
+                pass.line_start = 0;
+                return Some(pass.pos);
+            }
+        }
+        None
+    })
+}
+
+// the byte ranges of the comments in `input`.
+pub fn comments(input: &BStr) -> Vec<Range<usize>> {
+    let mut pass = Fused::new(input);
+    while pass.line() {}
+    if let Some(start) = pass.comment_start {
+        pass.comment_spans.push(start..input.len());
+    }
+    pass.comment_spans
+}
+
+struct Fused<'a> {
+    input: &'a BStr,
+    pos: usize,
+    text: Vec<u8>,
+    comments: CommentState,
+    line_start: usize,
+    // offset of the last character that wasn't part of a splice
+    prev_pos: usize,
+    comment_start: Option<usize>,
+    comment_spans: Vec<Range<usize>>,
+    preserve_line_numbers: bool,
+    // physical line breaks swallowed by splices and block comments since the
+    // last line was written
+    joined_lines: usize,
+}
+
+impl<'a> Fused<'a> {
+    fn new(input: &'a BStr) -> Self {
+        Self {
+            input,
+            pos: 0,
+            text: Vec::with_capacity(input.len() + 1),
+            comments: CommentState::new(),
+            line_start: 0,
+            prev_pos: 0,
+            comment_start: None,
+            comment_spans: vec![],
+            preserve_line_numbers: false,
+            joined_lines: 0,
+        }
+    }
+
+    // process up to and including the next line break. false once the input
+    // is used up.
+    fn line(&mut self) -> bool {
+        // whether any byte of the current physical line has been read
+        let mut line_open = false;
+        while let Some(&ch) = self.input.get(self.pos) {
+            match ch {
+                b'\\' => {
+                    if let Some(len) = splice_len(&self.input[self.pos + 1..]) {
+                        if len > 0 {
+                            self.joined_lines += 1;
+                        }
+                        self.pos += 1 + len;
+                        line_open = true;
+                        continue;
+                    }
+                }
+                b'\n' => {
+                    self.end_line();
+                    self.pos += 1;
+                    return true;
+                }
+                // a carriage return before a newline or at the very end is
+                // part of the line break
+                b'\r' if matches!(self.input.get(self.pos + 1), Some(b'\n') | None) => {
+                    self.end_line();
+                    self.pos = (self.pos + 2).min(self.input.len());
+                    return true;
+                }
+                _ => {}
+            }
+            self.char(ch);
+            line_open = true;
+            self.pos += 1;
+        }
+        if line_open {
+            self.end_line();
+        }
+        false
+    }
+
+    fn is_clean(&self) -> bool {
+        !self.comments.in_block_comment && !self.comments.in_string && !self.comments.in_char
+    }
+
+    fn finish(mut self) -> BString {
+        // an unterminated block comment swallows the line it started on
+        if self.comments.in_block_comment {
+            self.text.truncate(self.line_start);
+        }
+        self.text.into()
+    }
+
+    fn char(&mut self, ch: u8) {
+        let in_block_comment = self.comments.in_block_comment;
+        if let Some(emit) = should_emit(ch, &mut self.comments) {
+            if emit.pop_count > 0 {
+                self.comment_start = Some(self.prev_pos);
+            }
+            self.emit(emit);
+        }
+        if in_block_comment && !self.comments.in_block_comment {
+            self.end_comment(self.pos + 1);
+            // the comment is a space now; its closing `/` starts nothing
+            self.comments.prev_char = b' ';
+        } else {
+            self.comments.prev_char = ch;
+        }
+        self.prev_pos = self.pos;
+    }
+
+    // called with `pos` at the line break.
+    fn end_line(&mut self) {
+        let in_line_comment = self.comments.in_line_comment;
+        if let Some(emit) = should_emit(b'\n', &mut self.comments) {
+            if emit.ch != b'\n' {
+                self.emit(emit);
+            }
+        }
+        if in_line_comment && !self.comments.in_line_comment {
+            self.end_comment(self.pos);
+        }
+        self.comments.prev_char = b'\n';
+        if self.comments.in_block_comment {
+            self.joined_lines += 1;
+        } else {
+            self.text.push(b'\n');
+            if self.preserve_line_numbers {
+                self.text
+                    .extend(std::iter::repeat_n(b'\n', self.joined_lines));
+            }
+            self.joined_lines = 0;
+            self.line_start = self.text.len();
+        }
+    }
+
+    fn end_comment(&mut self, end: usize) {
+        if let Some(start) = self.comment_start.take() {
+            self.comment_spans.push(start..end);
+        }
+    }
+
+    fn emit(&mut self, Emit { ch, pop_count }: Emit) {
+        // the current character was never written, so it is one of the
+        // popped ones.
+        let popped = pop_count.saturating_sub(1);
+        self.text.truncate(self.text.len().saturating_sub(popped));
+        self.text.push(ch);
+    }
+}
+
+// whether `text` ends inside a C++ raw string literal. phase 3 doesn't know
+// about them, so the quotes in `R"(a"b)"` look like any other.
+fn in_raw_string(text: &[u8]) -> bool {
+    let mut pos = 0;
+    while let Some(offset) = text[pos..]
+        .iter()
+        .position(|&ch| matches!(ch, b'"' | b'\''))
+    {
+        let quote = pos + offset;
+        let word = word_before(&text[..quote]);
+        pos = quote + 1;
+        if text[quote] == b'\'' && word.first().is_some_and(u8::is_ascii_digit) {
+            // a digit separator
+            continue;
+        }
+        if text[quote] == b'"' && matches!(word, b"R" | b"u8R" | b"uR" | b"UR" | b"LR") {
+            let rest = &text[pos..];
+            let delim = rest
+                .iter()
+                .position(|&ch| ch == b'(')
+                .map(|len| &rest[..len]);
+            if let Some(delim) = delim.filter(|delim| is_raw_delimiter(delim)) {
+                let body = &rest[delim.len() + 1..];
+                let close = body.windows(delim.len() + 2).position(|w| {
+                    w[0] == b')' && &w[1..=delim.len()] == delim && w[delim.len() + 1] == b'"'
+                });
+                match close {
+                    Some(close) => pos += delim.len() + 1 + close + delim.len() + 2,
+                    None => return true,
+                }
+                continue;
+            }
+        }
+        // an ordinary literal, which ends with its line at the latest
+        let mut escaped = false;
+        while let Some(&ch) = text.get(pos) {
+            pos += 1;
+            match ch {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'\n' => break,
+                _ if ch == text[quote] => break,
+                _ => {}
+            }
+        }
+    }
+    false
+}
+
+// the identifier or pp-number characters just before the end of `text`.
+fn word_before(text: &[u8]) -> &[u8] {
+    let start = text
+        .iter()
+        .rposition(|&ch| !(ch.is_ascii_alphanumeric() || matches!(ch, b'_' | b'.' | b'\'')))
+        .map_or(0, |pos| pos + 1);
+    &text[start..]
+}
+
+fn is_raw_delimiter(delim: &[u8]) -> bool {
+    delim.len() <= 16
+        && !delim
+            .iter()
+            .any(|&ch| matches!(ch, b' ' | b'\t' | b'\n' | b'\r' | b')' | b'\\' | b'"'))
+}
+
+// the length of the line break after a backslash, if it splices.
+fn splice_len(rest: &[u8]) -> Option<usize> {
+    match rest {
+        [] => Some(0),
+        [b'\n', ..] | [b'\r'] => Some(1),
+        [b'\r', b'\n', ..] => Some(2),
+        _ => None,
+    }
+}
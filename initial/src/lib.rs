@@ -1,2 +1,6 @@
+mod comment;
+pub mod fused;
 mod line;
 pub mod lines;
+pub mod source_map;
+pub mod warning;
@@ -1,25 +1,12 @@
-use std::borrow::Cow;
-
 use bstr::BStr;
-use bstr::BString;
 use itertools::izip;
 
-pub(crate) struct Line<'a> {
-    pub(crate) text: Cow<'a, BStr>,
-    pub(crate) trivial: Vec<bool>,
-    pub(crate) synthetic: Vec<bool>,
-}
-
-pub(crate) struct LineBuilder<'a> {
-    text: Cow<'a, BStr>,
-    trivial: Option<Vec<bool>>,
-    synthetic: Option<Vec<bool>>,
-}
-
-pub(crate) struct OwnedLine {
-    pub(crate) text: BString,
-    pub(crate) trivial: Vec<bool>,
-    pub(crate) synthetic: Vec<bool>,
+// a view of one logical line inside `Lines`.
+pub(crate) struct Line<'b> {
+    pub(crate) text: &'b BStr,
+    pub(crate) trivial: &'b [bool],
+    pub(crate) synthetic: &'b [bool],
+    pub(crate) origin: &'b Origin,
 }
 
 #[derive(Clone, Copy)]
@@ -29,6 +16,15 @@ pub(crate) struct CharInfo {
     pub(crate) synthetic: bool,
 }
 
+// the physical lines a logical line was assembled from.
+#[derive(Clone, Default)]
+pub(crate) struct Origin {
+    // 0-based physical line of the first character
+    pub(crate) first_line: usize,
+    // (index into the text, physical line) for every later physical line
+    pub(crate) splices: Vec<(usize, usize)>,
+}
+
 impl CharInfo {
     pub(crate) fn new(ch: u8, trivial: bool, synthetic: bool) -> Self {
         Self {
@@ -39,56 +35,35 @@ impl CharInfo {
     }
 }
 
-impl OwnedLine {
-    pub(crate) fn empty() -> Self {
+impl Origin {
+    pub(crate) fn new(first_line: usize) -> Self {
         Self {
-            text: vec![].into(),
-            trivial: vec![],
-            synthetic: vec![],
+            first_line,
+            splices: vec![],
         }
     }
 
-    pub(crate) fn to_line(&mut self) -> Line<'static> {
-        let mut temp = OwnedLine::empty();
-        std::mem::swap(self, &mut temp);
-        Line::new(Cow::Owned(temp.text))
-            .with_synthetic(temp.synthetic)
-            .with_trivial(temp.trivial)
-            .build()
-    }
-
-    pub(crate) fn push(&mut self, info: CharInfo) {
-        self.text.push(info.ch);
-        self.trivial.push(info.trivial);
-        self.synthetic.push(info.synthetic);
+    // record that the line `other` was joined on at `offset`.
+    pub(crate) fn append(&mut self, offset: usize, other: &Origin) {
+        self.splices.push((offset, other.first_line));
+        self.splices.extend(
+            other
+                .splices
+                .iter()
+                .map(|&(start, physical)| (offset + start, physical)),
+        );
     }
 }
 
-impl<'a> Line<'a> {
-    pub(crate) fn new(data: Cow<'a, BStr>) -> LineBuilder<'a> {
-        LineBuilder {
-            text: data,
-            trivial: None,
-            synthetic: None,
-        }
-    }
-
-    pub(crate) fn empty() -> Self {
-        Self {
-            text: Cow::Borrowed(BStr::new(b"")),
-            trivial: vec![],
-            synthetic: vec![],
-        }
-    }
-
-    pub(crate) fn to_non_trivial(&self) -> impl Iterator<Item = u8> + '_ {
+impl<'b> Line<'b> {
+    pub(crate) fn to_non_trivial(&self) -> impl Iterator<Item = u8> + 'b {
         self.text
             .iter()
             .zip(self.trivial.iter())
-            .filter_map(|(&ch, trivial)| (!trivial).then(|| ch))
+            .filter_map(|(&ch, trivial)| (!trivial).then_some(ch))
     }
 
-    pub(crate) fn chars(&self) -> impl Iterator<Item = CharInfo> + '_ {
+    pub(crate) fn chars(&self) -> impl Iterator<Item = CharInfo> + 'b {
         izip!(
             self.text.iter().copied(),
             self.trivial.iter().copied(),
@@ -97,25 +72,3 @@ impl<'a> Line<'a> {
         .map(|(ch, trivial, synthetic)| CharInfo::new(ch, trivial, synthetic))
     }
 }
-
-impl<'a> LineBuilder<'a> {
-    pub(crate) fn with_trivial(mut self, trivial: Vec<bool>) -> Self {
-        self.trivial = Some(trivial);
-        self
-    }
-
-    pub(crate) fn with_synthetic(mut self, synthetic: Vec<bool>) -> Self {
-        self.synthetic = Some(synthetic);
-        self
-    }
-
-    pub(crate) fn build(self) -> Line<'a> {
-        Line {
-            trivial: self.trivial.unwrap_or_else(|| vec![false; self.text.len()]),
-            synthetic: self
-                .synthetic
-                .unwrap_or_else(|| vec![false; self.text.len()]),
-            text: self.text,
-        }
-    }
-}
@@ -1,194 +1,334 @@
 use std::borrow::Cow;
+use std::ops::Range;
 
 use bstr::BStr;
 use bstr::BString;
 use bstr::ByteSlice;
+use itertools::Itertools;
 
+use crate::comment::should_emit;
+use crate::comment::CommentState;
+use crate::comment::Emit;
 use crate::line::CharInfo;
 use crate::line::Line;
-use crate::line::OwnedLine;
+use crate::line::Origin;
+use crate::source_map::Location;
+use crate::source_map::SourceMap;
+use crate::warning::Warning;
 
-pub struct Lines<'a>(Vec<Line<'a>>);
+// all lines share one text buffer, with a parallel flag for every byte.
+// until comments are deleted the buffer is the input itself.
+pub struct Lines<'a> {
+    text: Cow<'a, BStr>,
+    trivial: Vec<bool>,
+    synthetic: Vec<bool>,
+    // where each logical line lives in `text`
+    ranges: Vec<Range<usize>>,
+    origins: Vec<Origin>,
+    physical_line_count: usize,
+    final_newline: bool,
+    pedantic: bool,
+    preserve_line_numbers: bool,
+    warnings: Vec<Warning>,
+}
 
 impl<'a> Lines<'a> {
     pub fn new(input: &'a BStr) -> Self {
-        Self(
-            input
-                .lines()
-                .map(|line| Line::new(Cow::Borrowed(line.into())).build())
-                .collect(),
-        )
+        let line_count = input.iter().filter(|&&ch| ch == b'\n').count() + 1;
+        let mut ranges = Vec::with_capacity(line_count);
+        let mut start = 0;
+        for line in input.lines_with_terminator() {
+            let content = line.trim_end_with(|c| c == '\n');
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            ranges.push(start..start + content.len());
+            start += line.len();
+        }
+        Self {
+            text: Cow::Borrowed(input),
+            trivial: vec![false; input.len()],
+            synthetic: vec![false; input.len()],
+            origins: (0..ranges.len()).map(Origin::new).collect(),
+            physical_line_count: ranges.len(),
+            ranges,
+            final_newline: input.is_empty() || input.ends_with(b"\n"),
+            pedantic: false,
+            preserve_line_numbers: false,
+            warnings: vec![],
+        }
+    }
+
+    // report the conditions the standard calls out but which are otherwise
+    // silently normalized. must come before the other phases.
+    pub fn pedantic(mut self) -> Self {
+        self.pedantic = true;
+        if !self.final_newline {
+            self.warnings.push(Warning::NoNewlineAtEof {
+                line: self.ranges.len() - 1,
+            });
+        }
+        self
+    }
+
+    // have `finish` emit an empty line for every physical line that was
+    // joined onto the one before it, so output line n is input line n.
+    pub fn preserve_line_numbers(mut self) -> Self {
+        self.preserve_line_numbers = true;
+        self
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    fn line(&self, i: usize) -> Line<'_> {
+        let range = self.ranges[i].clone();
+        Line {
+            text: self.text[range.clone()].as_bstr(),
+            trivial: &self.trivial[range.clone()],
+            synthetic: &self.synthetic[range],
+            origin: &self.origins[i],
+        }
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub fn merge_escaped_newlines(mut self) -> Self {
-        let mut builder = OwnedLine::empty();
-        let mut write_idx = 0;
-        for rd in 0..self.0.len() {
-            let mut line = Line::empty();
-            std::mem::swap(&mut self.0[rd], &mut line);
-            if line.text.ends_with_str("\\") {
-                builder.text.extend_from_slice(&line.text);
-                builder.synthetic.extend_from_slice(&line.synthetic);
-                builder.trivial.extend(
-                    line.trivial
-                        .iter()
-                        .take(line.trivial.len() - 2)
-                        .chain([true, true].iter()),
-                );
-            } else {
-                if builder.text.is_empty() {
-                    self.0[write_idx] = line;
-                } else {
-                    builder.text.extend_from_slice(&line.text);
-                    builder.trivial.extend_from_slice(&line.trivial);
-                    builder.synthetic.extend_from_slice(&line.synthetic);
-                    self.0[write_idx] = builder.to_line();
+        let old_ranges = std::mem::take(&mut self.ranges);
+        let old_origins = std::mem::take(&mut self.origins);
+        self.ranges.reserve(old_ranges.len());
+        self.origins.reserve(old_origins.len());
+        let mut pending: Option<(Range<usize>, Origin)> = None;
+        for (range, origin) in old_ranges.into_iter().zip(old_origins) {
+            let (range, origin) = match pending.take() {
+                Some((prev, mut merged)) => {
+                    // the line break between the two is spliced out too.
+                    self.trivial[prev.end..range.start].fill(true);
+                    merged.append(range.start - prev.start, &origin);
+                    (prev.start..range.end, merged)
                 }
-                write_idx += 1;
+                None => (range, origin),
+            };
+            if self.text[range.clone()].ends_with(b"\\") {
+                self.trivial[range.end - 1] = true;
+                pending = Some((range, origin));
+            } else {
+                self.ranges.push(range);
+                self.origins.push(origin);
             }
         }
-        if !builder.text.is_empty() {
-            self.0[write_idx] = builder.to_line();
-            write_idx += 1;
+        if let Some((range, origin)) = pending {
+            if self.pedantic {
+                self.warnings.push(Warning::BackslashNewlineAtEof {
+                    line: self.physical_line_count - 1,
+                });
+            }
+            self.ranges.push(range);
+            self.origins.push(origin);
         }
-        self.0.truncate(write_idx);
         self
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub fn delete_comments(mut self) -> Self {
-        let mut builder = OwnedLine::empty();
+        // every byte is kept, plus a space for each comment
+        let mut out = Buffer::with_capacity(self.text.len());
+        let mut ranges = Vec::with_capacity(self.ranges.len());
+        let mut origins = Vec::with_capacity(self.ranges.len());
+        // a block comment can carry one output line across several input
+        // lines.
+        let mut line_start = 0;
+        let mut origin: Option<Origin> = None;
         let mut comments = CommentState::new();
-        let mut wr = 0;
-        for rd in 0..self.0.len() {
-            let mut line = Line::empty();
-            std::mem::swap(&mut self.0[rd], &mut line);
+        for i in 0..self.ranges.len() {
+            // moved out rather than cloned; the line's own is left empty
+            let line_origin = std::mem::take(&mut self.origins[i]);
+            let line = self.line(i);
+            match &mut origin {
+                Some(origin) => origin.append(out.text.len() - line_start, &line_origin),
+                None => origin = Some(line_origin),
+            }
             for info in line.chars() {
-                builder.push(info);
+                out.push(info);
+                // already removed by an earlier phase
+                if info.trivial {
+                    continue;
+                }
 
+                let in_block_comment = comments.in_block_comment;
                 if let Some(Emit { ch, pop_count }) = should_emit(info.ch, &mut comments) {
-                    backtrack(&mut builder, pop_count);
+                    backtrack(&mut out.trivial[line_start..], pop_count);
                     if ch != info.ch {
-                        builder.push(CharInfo::new(ch, false, true));
+                        out.push(CharInfo::new(ch, false, true));
                     }
-                } else {
-                    *builder.trivial.last_mut().unwrap() = true;
-                }
-                if !info.trivial {
-                    comments.prev_char = info.ch;
+                } else if let Some(trivial) = out.trivial.last_mut() {
+                    *trivial = true;
                 }
+                // the comment is a space now; its closing `/` starts nothing
+                comments.prev_char = if in_block_comment && !comments.in_block_comment {
+                    b' '
+                } else {
+                    info.ch
+                };
             }
             if let Some(Emit { ch, pop_count }) = should_emit(b'\n', &mut comments) {
-                backtrack(&mut builder, pop_count);
+                backtrack(&mut out.trivial[line_start..], pop_count);
                 if ch != b'\n' {
-                    builder.push(CharInfo::new(ch, false, true));
+                    out.push(CharInfo::new(ch, false, true));
                 }
-            } else {
-                *builder.trivial.last_mut().unwrap() = true;
             }
             comments.prev_char = b'\n';
             if !comments.in_block_comment {
-                self.0[wr] = builder.to_line();
-                wr += 1;
+                ranges.push(line_start..out.text.len());
+                origins.extend(origin.take());
+                line_start = out.text.len();
             }
         }
-        self.0.truncate(wr);
+        self.text = Cow::Owned(out.text.into());
+        self.trivial = out.trivial;
+        self.synthetic = out.synthetic;
+        self.ranges = ranges;
+        self.origins = origins;
         self
     }
 
+    // the 0-based physical line each logical line starts on.
+    pub fn physical_lines(&self) -> impl Iterator<Item = usize> + '_ {
+        self.origins.iter().map(|origin| origin.first_line)
+    }
+
+    // how many placeholder lines follow each logical line in the output.
+    fn placeholder_counts(&self) -> Vec<usize> {
+        if !self.preserve_line_numbers {
+            return vec![0; self.ranges.len()];
+        }
+        self.physical_lines()
+            .chain([self.physical_line_count])
+            .tuple_windows()
+            .map(|(first, next)| next - first - 1)
+            .collect()
+    }
+
+    // enough for the output of `finish`: every byte of text, a line break
+    // for each line and the placeholder lines.
+    fn output_capacity(&self, placeholders: &[usize]) -> usize {
+        self.text.len() + self.ranges.len() + placeholders.iter().sum::<usize>()
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub fn finish(self) -> BString {
-        self.0
-            .into_iter()
-            .fold(vec![], |mut acc, line| {
+        let placeholders = self.placeholder_counts();
+        let out = Vec::with_capacity(self.output_capacity(&placeholders));
+        (0..self.ranges.len())
+            .map(|i| self.line(i))
+            .zip(placeholders)
+            .fold(out, |mut acc, (line, placeholders)| {
                 acc.extend(line.to_non_trivial());
                 acc.push(b'\n');
+                acc.extend(std::iter::repeat_n(b'\n', placeholders));
                 acc
             })
             .into()
     }
-}
-
-struct CommentState {
-    in_string: bool,
-    in_block_comment: bool,
-    in_line_comment: bool,
-    prev_char: u8,
-}
 
-struct Emit {
-    ch: u8,
-    pop_count: usize,
-}
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn finish_with_source_map(self) -> (BString, SourceMap) {
+        let placeholders = self.placeholder_counts();
+        let capacity = self.output_capacity(&placeholders);
+        let mut out = Vec::with_capacity(capacity);
+        let lines = placeholders.len() + placeholders.iter().sum::<usize>();
+        let mut map = SourceMap::with_capacity(capacity, lines);
+        let mut logical_line = 0;
+        for (i, placeholders) in placeholders.into_iter().enumerate() {
+            let line = self.line(i);
+            map.start_line();
+            // synthetic characters were never in the original line; they
+            // stand in for the comment that precedes them, so they take the
+            // column where it started.
+            let mut physical_line = line.origin.first_line;
+            let mut splices = line.origin.splices.iter().peekable();
+            let mut column = 0;
+            let mut trivial_start = None;
+            for (i, info) in line.chars().enumerate() {
+                if let Some(&(_, start)) = splices.next_if(|&&(offset, _)| offset == i) {
+                    physical_line = start;
+                    column = 0;
+                    trivial_start = None;
+                }
+                if !info.trivial {
+                    out.push(info.ch);
+                    let column = if info.synthetic {
+                        trivial_start.unwrap_or(column)
+                    } else {
+                        column
+                    };
+                    map.push(Location {
+                        logical_line,
+                        line: physical_line,
+                        column,
+                    });
+                }
+                if !info.synthetic {
+                    if info.trivial {
+                        trivial_start.get_or_insert(column);
+                    } else {
+                        trivial_start = None;
+                    }
+                    column += 1;
+                }
+            }
+            out.push(b'\n');
+            map.push(Location {
+                logical_line,
+                line: physical_line,
+                column,
+            });
+            logical_line += 1;
 
-impl CommentState {
-    fn new() -> Self {
-        Self {
-            in_string: false,
-            in_block_comment: false,
-            in_line_comment: false,
-            prev_char: 0,
+            for i in 0..placeholders {
+                map.start_line();
+                out.push(b'\n');
+                map.push(Location {
+                    logical_line,
+                    line: line.origin.first_line + 1 + i,
+                    column: 0,
+                });
+                logical_line += 1;
+            }
         }
+        (out.into(), map)
     }
 }
 
-impl Emit {
-    fn new(ch: u8) -> Self {
-        Self { ch, pop_count: 0 }
-    }
+struct Buffer {
+    text: Vec<u8>,
+    trivial: Vec<bool>,
+    synthetic: Vec<bool>,
 }
 
-fn should_emit(ch: u8, comments: &mut CommentState) -> Option<Emit> {
-    if comments.in_string {
-        if ch == b'"' && comments.prev_char != b'\\' {
-            comments.in_string = false;
-        }
-        Some(Emit::new(ch))
-    } else if comments.in_block_comment && ch == b'/' && comments.prev_char == b'*' {
-        comments.in_block_comment = false;
-        None
-    } else if comments.in_line_comment && ch == b'\n' {
-        comments.in_line_comment = false;
-        Some(Emit::new(ch))
-    } else if comments.in_line_comment || comments.in_block_comment {
-        None
-    } else {
-        match ch {
-            b'/' => {
-                if comments.prev_char == b'/' {
-                    comments.in_line_comment = true;
-                    Some(Emit {
-                        ch: b' ',
-                        pop_count: 2,
-                    })
-                } else {
-                    Some(Emit::new(ch))
-                }
-            }
-            b'*' => {
-                if comments.prev_char == b'/' {
-                    comments.in_block_comment = true;
-                    Some(Emit {
-                        ch: b' ',
-                        pop_count: 2,
-                    })
-                } else {
-                    Some(Emit::new(ch))
-                }
-            }
-            b'"' => {
-                comments.in_string = !comments.in_string;
-                Some(Emit::new(ch))
-            }
-            _ => Some(Emit::new(ch)),
+impl Buffer {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            text: Vec::with_capacity(capacity),
+            trivial: Vec::with_capacity(capacity),
+            synthetic: Vec::with_capacity(capacity),
         }
     }
+
+    fn push(&mut self, info: CharInfo) {
+        self.text.push(info.ch);
+        self.trivial.push(info.trivial);
+        self.synthetic.push(info.synthetic);
+    }
 }
 
-fn backtrack(builder: &mut OwnedLine, pop_count: usize) {
-    let mut i = builder.text.len();
+// marks the last `pop_count` characters not already trivial as trivial, or
+// as many as the line has.
+fn backtrack(trivial: &mut [bool], pop_count: usize) {
+    let mut end = trivial.len();
     for _ in 0..pop_count {
-        while i > 0 && builder.trivial[i - 1] {
-            i -= 1;
-        }
-        builder.trivial[i - 1] = true;
+        let Some(i) = trivial[..end].iter().rposition(|&t| !t) else {
+            break;
+        };
+        trivial[i] = true;
+        end = i;
     }
 }
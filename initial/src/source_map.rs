@@ -0,0 +1,44 @@
+// maps byte offsets in the output of `Lines::finish_with_source_map` back to
+// the text they came from, skipping the spaces that replaced comments.
+pub struct SourceMap {
+    // offset of the first byte of each output line
+    line_starts: Vec<usize>,
+    // one entry for every output byte, including the line's `\n`
+    locations: Vec<Location>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    // 0-based index of the line in the output
+    pub logical_line: usize,
+    // 0-based physical line and column in the original input
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceMap {
+    // room for `bytes` bytes of output on `lines` lines.
+    pub(crate) fn with_capacity(bytes: usize, lines: usize) -> Self {
+        Self {
+            line_starts: Vec::with_capacity(lines),
+            locations: Vec::with_capacity(bytes),
+        }
+    }
+
+    pub(crate) fn start_line(&mut self) {
+        self.line_starts.push(self.locations.len());
+    }
+
+    pub(crate) fn push(&mut self, location: Location) {
+        self.locations.push(location);
+    }
+
+    pub fn locate(&self, offset: usize) -> Option<Location> {
+        self.locations.get(offset).copied()
+    }
+
+    // the output offset where a logical line starts.
+    pub fn line_start(&self, logical_line: usize) -> Option<usize> {
+        self.line_starts.get(logical_line).copied()
+    }
+}
@@ -0,0 +1,17 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Warning {
+    #[error("line {}: no newline at end of file", line + 1)]
+    NoNewlineAtEof { line: usize },
+    #[error("line {}: backslash-newline at end of file", line + 1)]
+    BackslashNewlineAtEof { line: usize },
+}
+
+impl Warning {
+    // stable across releases; a retired code is never reused.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NoNewlineAtEof { .. } => "PP2001",
+            Self::BackslashNewlineAtEof { .. } => "PP2002",
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use bstr::BStr;
+
+use initial::fused;
+use initial::lines::Lines;
+
+const SRC: &str = "#define A 1 \\\n  2\n/* a\nb */ x /* c\n*/ y\nz\n";
+
+#[test]
+fn joined_lines_come_back_empty() {
+    let text = Lines::new(BStr::new(SRC))
+        .merge_escaped_newlines()
+        .delete_comments()
+        .preserve_line_numbers()
+        .finish();
+    assert_eq!(text, "#define A 1   2\n\n  x   y\n\n\nz\n");
+    assert_eq!(fused::process_preserving_lines(BStr::new(SRC)), text);
+}
+
+#[test]
+fn quotes_in_character_constants() {
+    let src = "a = '\"'; /* b */\nc = 1'000; // d\ne = '\\''; f // g\n";
+    let text = Lines::new(BStr::new(src)).delete_comments().finish();
+    assert_eq!(text, "a = '\"';  \nc = 1'000;  \ne = '\\''; f  \n");
+    assert_eq!(fused::process(BStr::new(src)), text);
+    assert_eq!(
+        fused::split_points(BStr::new(src)).collect::<Vec<_>>(),
+        [17, 33, 50]
+    );
+}
+
+#[test]
+fn no_split_points_inside_raw_strings() {
+    let src = "x\ns = R\"x(a\"b\nc'd\n)x\";\ny\n";
+    assert_eq!(
+        fused::split_points(BStr::new(src)).collect::<Vec<_>>(),
+        [2, 23, 25]
+    );
+}
+
+#[test]
+fn source_map_columns() {
+    let src = "a /* c */ b\nx \\\n  y\n";
+    let (text, map) = Lines::new(BStr::new(src))
+        .merge_escaped_newlines()
+        .delete_comments()
+        .finish_with_source_map();
+    assert_eq!(text, "a   b\nx   y\n");
+    let at = |offset| {
+        let location = map.locate(offset).unwrap();
+        (location.logical_line, location.line, location.column)
+    };
+    // the space standing in for the comment is where the comment started
+    assert_eq!(at(2), (0, 0, 2));
+    assert_eq!(at(4), (0, 0, 10));
+    // after a splice, columns count on the physical line
+    assert_eq!(map.line_start(1), Some(6));
+    assert_eq!(at(6), (1, 1, 0));
+    assert_eq!(at(10), (1, 2, 2));
+}
+
+#[test]
+fn physical_line_of_each_logical_line() {
+    let src = "a \\\nb\nc /*\n*/ d\n\\\ne\n";
+    let lines = Lines::new(BStr::new(src))
+        .merge_escaped_newlines()
+        .delete_comments();
+    assert_eq!(lines.physical_lines().collect::<Vec<_>>(), [0, 2, 4]);
+    assert_eq!(lines.finish(), "a b\nc   d\ne\n");
+}
+
+#[test]
+fn fused_matches_lines() {
+    let cases = [
+        "",
+        "a",
+        "a\r\nb\r\n",
+        "a\rb\n",
+        "a /* b */ c // d\ne\n",
+        "a /\\\n* b *\\\n/ c\n",
+        "a //\\\nb\nc\n",
+        "/* a\n\n*/ b /* c */\n",
+        "a /* unterminated\nb\n",
+        "\"/* a */\" '//' b\n",
+        "a \\\r\nb\\",
+        "a \\ \nb\n",
+        "x = 1'0; /* c */\n",
+    ];
+    for src in cases {
+        let src = BStr::new(src);
+        let lines = || Lines::new(src).merge_escaped_newlines().delete_comments();
+        assert_eq!(fused::process(src), lines().finish(), "{src:?}");
+        assert_eq!(
+            fused::process_preserving_lines(src),
+            lines().preserve_line_numbers().finish(),
+            "{src:?}"
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use bstr::BStr;
+
+use crate::pragma::Pragma;
+use crate::token::Token;
+
+pub trait Callbacks {
+    // `#ident "text"` or `#sccs "text"`, with the quotes still attached.
+    fn ident(&mut self, _text: &BStr) {}
+
+    // a C++20 `module`, `export module` or `import` line, without its `Eol`.
+    fn module_directive(&mut self, _tokens: &[Token<'_>]) {}
+
+    // returns whether the pragma was handled here; unhandled `Pragma::Other`s
+    // are reported under `-Wunknown-pragmas`.
+    fn pragma(&mut self, _pragma: &Pragma<'_>) -> bool {
+        false
+    }
+}
+
+// an extension directive, registered with `Parser::with_directive`.
+pub trait DirectiveHandler {
+    // the directive's tokens after its name, without the `Eol`. an `Err` is
+    // reported as an error at the directive.
+    fn handle(&mut self, line: &[Token<'_>]) -> Result<(), String>;
+}
+
+impl<F> DirectiveHandler for F
+where
+    F: FnMut(&[Token<'_>]) -> Result<(), String>,
+{
+    fn handle(&mut self, line: &[Token<'_>]) -> Result<(), String> {
+        self(line)
+    }
+}
+
+pub struct NoCallbacks;
+
+impl Callbacks for NoCallbacks {}
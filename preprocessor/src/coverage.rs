@@ -0,0 +1,65 @@
+use std::fmt;
+
+use bstr::BString;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupState {
+    // the condition held and the group was processed
+    Entered,
+    // the condition was evaluated and failed
+    Skipped,
+    // an earlier group was taken or the enclosing group was skipped
+    NotEvaluated,
+    // this condition, or one that decided whether it was reached, invokes a
+    // function-like macro, which can't be expanded yet. the group is skipped.
+    Unknown,
+}
+
+// one `#if`, `#ifdef`, `#ifndef`, `#elif` or `#else` group.
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub directive: &'static str,
+    pub file: Option<BString>,
+    pub line: u32,
+    pub state: GroupState,
+}
+
+// every conditional group seen, in source order.
+#[derive(Debug, Clone, Default)]
+pub struct CoverageReport {
+    pub groups: Vec<Group>,
+}
+
+impl CoverageReport {
+    pub(crate) fn push(&mut self, group: Group) {
+        self.groups.push(group);
+    }
+}
+
+impl fmt::Display for GroupState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Entered => "entered",
+            Self::Skipped => "skipped",
+            Self::NotEvaluated => "not evaluated",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for group in &self.groups {
+            let file = group
+                .file
+                .as_ref()
+                .map_or("<stdin>".into(), |file| file.to_string());
+            writeln!(
+                f,
+                "{file}:{}: #{} {}",
+                group.line, group.directive, group.state
+            )?;
+        }
+        Ok(())
+    }
+}
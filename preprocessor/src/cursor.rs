@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+
+// a position to return to with `TokenCursor::rewind`.
+#[must_use]
+pub struct Checkpoint(usize);
+
+// a cursor over a token stream that supports speculative parsing: of
+// `Token`s from the lexer, or of the `Result`s the parser and `Normalized`
+// yield, errors and all. tokens are only buffered while a checkpoint is
+// outstanding or for lookahead.
+pub struct TokenCursor<Tokens>
+where
+    Tokens: Iterator,
+    Tokens::Item: Clone,
+{
+    tokens: Tokens,
+    buffer: VecDeque<Tokens::Item>,
+    // absolute index of `buffer[0]`
+    offset: usize,
+    // absolute index of the next token
+    pos: usize,
+    checkpoints: usize,
+    lookahead: usize,
+}
+
+impl<Tokens> TokenCursor<Tokens>
+where
+    Tokens: Iterator,
+    Tokens::Item: Clone,
+{
+    pub fn new(tokens: Tokens) -> Self {
+        Self {
+            tokens,
+            buffer: VecDeque::new(),
+            offset: 0,
+            pos: 0,
+            checkpoints: 0,
+            lookahead: 4,
+        }
+    }
+
+    // how far past the current token `peek_nth` may look.
+    pub fn with_lookahead(mut self, lookahead: usize) -> Self {
+        self.lookahead = lookahead;
+        self
+    }
+
+    pub fn peek(&mut self) -> Option<Tokens::Item> {
+        self.peek_nth(0)
+    }
+
+    // `None` at the end of the stream or beyond the lookahead limit.
+    pub fn peek_nth(&mut self, n: usize) -> Option<Tokens::Item> {
+        if n > self.lookahead {
+            return None;
+        }
+        self.fill(self.pos + n)
+    }
+
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints += 1;
+        Checkpoint(self.pos)
+    }
+
+    // go back to `checkpoint`, so the tokens after it are returned again.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.pos = checkpoint.0;
+        self.release();
+    }
+
+    // keep the tokens read since `checkpoint`.
+    pub fn commit(&mut self, _checkpoint: Checkpoint) {
+        self.release();
+    }
+
+    fn release(&mut self) {
+        self.checkpoints -= 1;
+        if self.checkpoints == 0 {
+            // nothing can rewind to before the current token any more
+            let consumed = self.pos - self.offset;
+            self.buffer.drain(..consumed);
+            self.offset += consumed;
+        }
+    }
+
+    // make sure the token at absolute index `index` is buffered.
+    fn fill(&mut self, index: usize) -> Option<Tokens::Item> {
+        while self.offset + self.buffer.len() <= index {
+            self.buffer.push_back(self.tokens.next()?);
+        }
+        self.buffer.get(index - self.offset).cloned()
+    }
+}
+
+impl<Tokens> Iterator for TokenCursor<Tokens>
+where
+    Tokens: Iterator,
+    Tokens::Item: Clone,
+{
+    type Item = Tokens::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tok = self.fill(self.pos)?;
+        self.pos += 1;
+        if self.checkpoints == 0 {
+            self.buffer.pop_front();
+            self.offset += 1;
+        }
+        Some(tok)
+    }
+}
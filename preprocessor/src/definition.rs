@@ -0,0 +1,143 @@
+use bstr::BStr;
+
+use crate::parser::ParseError;
+use crate::token::Punct;
+use crate::token::Token;
+
+// a macro as `#define` gives it, lexed once so expanding it needn't lex again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Macro<'a> {
+    // `None` for an object-like macro. a variadic macro's last parameter is
+    // `__VA_ARGS__`, or the name before a GNU `name...`.
+    pub params: Option<Vec<&'a BStr>>,
+    pub variadic: bool,
+    pub body: Vec<BodyToken<'a>>,
+}
+
+// one token of a replacement list, with parameters resolved to their index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyToken<'a> {
+    Token(Token<'a>),
+    // replaced by the argument for this parameter
+    Param(usize),
+    // `#param`
+    Stringify(usize),
+    // `##`
+    Paste,
+}
+
+impl<'a> Macro<'a> {
+    // `rest` is what follows the macro name: the parameter list, if
+    // `function_like`, then the replacement list.
+    pub fn parse(rest: &[Token<'a>], function_like: bool) -> Result<Self, ParseError> {
+        let (params, variadic, body) = if function_like {
+            let (params, variadic, body) = parse_params(&rest[1..])?;
+            (Some(params), variadic, body)
+        } else {
+            (None, false, rest)
+        };
+        let body = replacement_list(body, params.as_deref())?;
+        Ok(Self {
+            params,
+            variadic,
+            body,
+        })
+    }
+
+    pub fn is_function_like(&self) -> bool {
+        self.params.is_some()
+    }
+
+    // an object-like macro, as `-D` defines.
+    pub fn object_like(body: impl IntoIterator<Item = Token<'a>>) -> Self {
+        let body = body.into_iter().map(|tok| match tok {
+            Token::Punct(Punct::HashHash) => BodyToken::Paste,
+            tok => BodyToken::Token(tok),
+        });
+        Self {
+            params: None,
+            variadic: false,
+            body: body.collect(),
+        }
+    }
+}
+
+// the parameters after the `(`, whether the last is variadic, and the tokens
+// after the `)`.
+fn parse_params<'t, 'a>(
+    mut rest: &'t [Token<'a>],
+) -> Result<(Vec<&'a BStr>, bool, &'t [Token<'a>]), ParseError> {
+    let mut params = vec![];
+    let mut variadic = false;
+    if let [Token::Punct(Punct::RParen), body @ ..] = rest {
+        return Ok((params, variadic, body));
+    }
+    loop {
+        rest = match rest {
+            [Token::Punct(Punct::Ellipsis), tail @ ..] => {
+                params.push(BStr::new("__VA_ARGS__"));
+                variadic = true;
+                tail
+            }
+            [Token::Ident(name), tail @ ..] => {
+                if params.contains(name) {
+                    return Err(ParseError::DuplicateMacroParameter((*name).to_owned()));
+                }
+                params.push(name);
+                match tail {
+                    [Token::Punct(Punct::Ellipsis), tail @ ..] => {
+                        variadic = true;
+                        tail
+                    }
+                    _ => tail,
+                }
+            }
+            [tok, ..] => {
+                return Err(ParseError::ExpectedMacroParameter(
+                    tok.spelling().to_owned(),
+                ))
+            }
+            [] => return Err(ParseError::UnterminatedMacroParameters),
+        };
+        rest = match rest {
+            [Token::Punct(Punct::RParen), body @ ..] => return Ok((params, variadic, body)),
+            // nothing may follow the variadic parameter
+            [Token::Punct(Punct::Comma), tail @ ..] if !variadic => tail,
+            [tok, ..] => {
+                return Err(ParseError::ExpectedCommaInMacroParameters(
+                    tok.spelling().to_owned(),
+                ))
+            }
+            [] => return Err(ParseError::UnterminatedMacroParameters),
+        };
+    }
+}
+
+// `#` only stringifies in a function-like macro, where it must be followed by
+// a parameter.
+fn replacement_list<'a>(
+    body: &[Token<'a>],
+    params: Option<&[&BStr]>,
+) -> Result<Vec<BodyToken<'a>>, ParseError> {
+    let param = |tok: &Token<'_>| match (tok, params) {
+        (Token::Ident(name), Some(params)) => params.iter().position(|param| param == name),
+        _ => None,
+    };
+    let mut list = Vec::with_capacity(body.len());
+    let mut tokens = body.iter();
+    while let Some(tok) = tokens.next() {
+        list.push(match tok {
+            Token::Punct(Punct::HashHash) => BodyToken::Paste,
+            Token::Punct(Punct::Hash) if params.is_some() => tokens
+                .next()
+                .and_then(param)
+                .map(BodyToken::Stringify)
+                .ok_or(ParseError::StringifyWithoutParameter)?,
+            tok => param(tok).map_or(BodyToken::Token(*tok), BodyToken::Param),
+        });
+    }
+    if [list.first(), list.last()].contains(&Some(&BodyToken::Paste)) {
+        return Err(ParseError::PasteAtEdge);
+    }
+    Ok(list)
+}
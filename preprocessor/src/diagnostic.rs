@@ -0,0 +1,73 @@
+use std::fmt;
+
+use crate::parser::ParseError;
+use crate::warning::Warning;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// any error or warning, with the stable code identifying its kind.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(e: &ParseError) -> Self {
+        Self {
+            code: e.code(),
+            severity: Severity::Error,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(w: &Warning) -> Self {
+        Self {
+            code: w.code(),
+            severity: Severity::Warning,
+            message: w.to_string(),
+        }
+    }
+}
+
+impl Diagnostic {
+    // a warning that has been promoted with `-Werror` or a pragma.
+    pub(crate) fn promoted(w: &Warning) -> Self {
+        Self {
+            severity: Severity::Error,
+            ..Self::from(w)
+        }
+    }
+}
+
+impl From<&initial::warning::Warning> for Diagnostic {
+    fn from(w: &initial::warning::Warning) -> Self {
+        Self {
+            code: w.code(),
+            severity: Severity::Warning,
+            message: w.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        })
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
@@ -0,0 +1,373 @@
+use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice;
+
+use crate::literal;
+use crate::literal::CharError;
+use crate::literal::Encoding;
+use crate::number;
+use crate::number::IntegerError;
+use crate::number::IntegerType;
+use crate::parser::ParseError;
+use crate::target::Target;
+use crate::token::Punct;
+use crate::token::Token;
+
+type IsAsserted<'t> = dyn Fn(&BStr, Option<&BStr>) -> bool + 't;
+
+// every integer in #if is an `intmax_t` or a `uintmax_t`, both 64 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Value {
+    // read as a `u64` where `unsigned`
+    bits: i64,
+    unsigned: bool,
+}
+
+impl Value {
+    fn signed(bits: i64) -> Self {
+        Self {
+            bits,
+            unsigned: false,
+        }
+    }
+
+    fn from_bool(b: bool) -> Self {
+        Self::signed(b.into())
+    }
+
+    fn is_true(self) -> bool {
+        self.bits != 0
+    }
+}
+
+// evaluates the controlling expression of `#if` and `#elif`, once its macros
+// are expanded. every identifier left other than `defined`, and `true` and
+// `false` where they are keywords, counts as 0.
+pub(crate) struct Evaluator<'t, 'a, F> {
+    tokens: &'t [Token<'a>],
+    pos: usize,
+    is_defined: F,
+    // answers `#pred` and `#pred(answer)`, when assertions are enabled
+    is_asserted: Option<&'t IsAsserted<'t>>,
+    // `true` is 1, as in C23 and C++
+    boolean_literals: bool,
+    // decides the values of character constants
+    target: Target,
+    // how many enclosing operands are not evaluated, like the right of
+    // `0 && ...`; they are still parsed, but can't fail to evaluate
+    unevaluated: usize,
+}
+
+impl<'t, 'a, F> Evaluator<'t, 'a, F>
+where
+    F: Fn(&BStr) -> bool,
+{
+    pub(crate) fn new(tokens: &'t [Token<'a>], is_defined: F) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            is_defined,
+            is_asserted: None,
+            boolean_literals: false,
+            target: Target::default(),
+            unevaluated: 0,
+        }
+    }
+
+    pub(crate) fn with_assertions(mut self, is_asserted: &'t IsAsserted<'t>) -> Self {
+        self.is_asserted = Some(is_asserted);
+        self
+    }
+
+    pub(crate) fn with_boolean_literals(mut self) -> Self {
+        self.boolean_literals = true;
+        self
+    }
+
+    pub(crate) fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub(crate) fn evaluate(mut self) -> Result<i64, ParseError> {
+        if self.tokens.is_empty() {
+            return Err(ParseError::MissingCondition);
+        }
+        let value = self.conditional()?;
+        match self.peek() {
+            None => Ok(value.bits),
+            Some(tok) => Err(ParseError::UnexpectedInCondition(tok.spelling().to_owned())),
+        }
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, punct: Punct) -> bool {
+        if self.peek() == Some(Token::Punct(punct)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: Punct) -> Result<(), ParseError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(ParseError::ExpectedInCondition(punct.spelling()))
+        }
+    }
+
+    fn conditional(&mut self) -> Result<Value, ParseError> {
+        let cond = self.binary(0)?;
+        if !self.eat(Punct::Question) {
+            return Ok(cond);
+        }
+        let then = self.unevaluated_if(!cond.is_true(), Self::conditional)?;
+        self.expect(Punct::Colon)?;
+        let otherwise = self.unevaluated_if(cond.is_true(), Self::conditional)?;
+        // the arms are converted to a common type, whichever is chosen
+        Ok(Value {
+            bits: if cond.is_true() { then } else { otherwise }.bits,
+            unsigned: then.unsigned || otherwise.unsigned,
+        })
+    }
+
+    fn unevaluated_if<T>(
+        &mut self,
+        unevaluated: bool,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        self.unevaluated += usize::from(unevaluated);
+        let result = parse(self);
+        self.unevaluated -= usize::from(unevaluated);
+        result
+    }
+
+    // precedence climbing over the binary operators, loosest first.
+    fn binary(&mut self, min_prec: u8) -> Result<Value, ParseError> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Punct(op)) = self.peek() {
+            let Some(prec) = precedence(op) else {
+                break;
+            };
+            if prec < min_prec {
+                break;
+            }
+            self.pos += 1;
+            let short_circuit = match op {
+                Punct::AmpAmp => !lhs.is_true(),
+                Punct::PipePipe => lhs.is_true(),
+                _ => false,
+            };
+            let rhs = self.unevaluated_if(short_circuit, |this| this.binary(prec + 1))?;
+            lhs = match apply(op, lhs, rhs) {
+                Err(ParseError::DivisionByZeroInCondition) if self.unevaluated > 0 => {
+                    Value::signed(0)
+                }
+                result => result?,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Value, ParseError> {
+        let tok = self.peek().ok_or(ParseError::MissingOperandInCondition)?;
+        self.pos += 1;
+        match tok {
+            Token::Punct(Punct::Plus) => self.unary(),
+            Token::Punct(Punct::Minus) => {
+                let value = self.unary()?;
+                Ok(Value {
+                    bits: value.bits.wrapping_neg(),
+                    ..value
+                })
+            }
+            Token::Punct(Punct::Tilde) => {
+                let value = self.unary()?;
+                Ok(Value {
+                    bits: !value.bits,
+                    ..value
+                })
+            }
+            Token::Punct(Punct::Bang) => Ok(Value::from_bool(!self.unary()?.is_true())),
+            Token::Punct(Punct::LParen) => {
+                let value = self.conditional()?;
+                self.expect(Punct::RParen)?;
+                Ok(value)
+            }
+            Token::Ident(name) if name == "defined" => self.defined(),
+            Token::Ident(name) if self.boolean_literals && name == "true" => Ok(Value::signed(1)),
+            Token::Punct(Punct::Hash) if self.is_asserted.is_some() => self.assertion(),
+            // the lexer leaves encoding prefixes as identifiers
+            Token::Ident(prefix) if matches!(prefix.as_bytes(), b"u8" | b"u" | b"U" | b"L") => {
+                match self.peek() {
+                    Some(Token::StringLit(spelling)) if spelling.starts_with(b"'") => {
+                        self.pos += 1;
+                        let spelling = [prefix.as_bytes(), spelling.as_bytes()].concat();
+                        char_value(&spelling, &self.target)
+                    }
+                    _ => Ok(Value::signed(0)),
+                }
+            }
+            Token::Ident(_) => Ok(Value::signed(0)),
+            Token::Number(spelling) => parse_integer(spelling, &self.target),
+            Token::StringLit(spelling) if spelling.starts_with(b"'") => {
+                char_value(spelling, &self.target)
+            }
+            _ => Err(ParseError::UnexpectedInCondition(tok.spelling().to_owned())),
+        }
+    }
+
+    // `defined X` or `defined ( X )`
+    fn defined(&mut self) -> Result<Value, ParseError> {
+        let paren = self.eat(Punct::LParen);
+        let Some(Token::Ident(name)) = self.peek() else {
+            return Err(ParseError::MissingDefinedOperand);
+        };
+        self.pos += 1;
+        if paren {
+            self.expect(Punct::RParen)?;
+        }
+        Ok(Value::from_bool((self.is_defined)(name)))
+    }
+
+    // `#pred` or `#pred(answer)`, with the answer spelled as `#assert`
+    // records it.
+    fn assertion(&mut self) -> Result<Value, ParseError> {
+        let Some(Token::Ident(predicate)) = self.peek() else {
+            return Err(ParseError::MissingPredicate);
+        };
+        self.pos += 1;
+        if !self.eat(Punct::LParen) {
+            return Ok(Value::from_bool(self.is_asserted.unwrap()(predicate, None)));
+        }
+        let mut answer = BString::from(vec![]);
+        let mut depth = 0usize;
+        loop {
+            let tok = self.peek().ok_or(ParseError::UnterminatedAnswer)?;
+            self.pos += 1;
+            match tok {
+                Token::Punct(Punct::RParen) if depth == 0 => break,
+                Token::Punct(Punct::RParen) => depth -= 1,
+                Token::Punct(Punct::LParen) => depth += 1,
+                _ => {}
+            }
+            if !answer.is_empty() {
+                answer.push(b' ');
+            }
+            answer.extend_from_slice(tok.spelling());
+        }
+        if answer.is_empty() {
+            return Err(ParseError::MissingAnswer);
+        }
+        Ok(Value::from_bool(self.is_asserted.unwrap()(
+            predicate,
+            Some(answer.as_ref()),
+        )))
+    }
+}
+
+fn precedence(op: Punct) -> Option<u8> {
+    Some(match op {
+        Punct::PipePipe => 0,
+        Punct::AmpAmp => 1,
+        Punct::Pipe => 2,
+        Punct::Caret => 3,
+        Punct::Amp => 4,
+        Punct::EqEq | Punct::BangEq => 5,
+        Punct::Lt | Punct::Gt | Punct::LtEq | Punct::GtEq => 6,
+        Punct::LtLt | Punct::GtGt => 7,
+        Punct::Plus | Punct::Minus => 8,
+        Punct::Star | Punct::Slash | Punct::Percent => 9,
+        _ => return None,
+    })
+}
+
+// with the usual arithmetic conversions: if either operand is unsigned, both
+// are.
+fn apply(op: Punct, lhs: Value, rhs: Value) -> Result<Value, ParseError> {
+    let unsigned = lhs.unsigned || rhs.unsigned;
+    let (l, r) = (lhs.bits, rhs.bits);
+    let (ul, ur) = (l as u64, r as u64);
+    let arithmetic = |bits| Value { bits, unsigned };
+    // the result of a shift has the type of its left operand
+    let shifted = |bits| Value { bits, ..lhs };
+    Ok(match op {
+        Punct::PipePipe => Value::from_bool(l != 0 || r != 0),
+        Punct::AmpAmp => Value::from_bool(l != 0 && r != 0),
+        Punct::Pipe => arithmetic(l | r),
+        Punct::Caret => arithmetic(l ^ r),
+        Punct::Amp => arithmetic(l & r),
+        Punct::EqEq => Value::from_bool(l == r),
+        Punct::BangEq => Value::from_bool(l != r),
+        Punct::Lt if unsigned => Value::from_bool(ul < ur),
+        Punct::Gt if unsigned => Value::from_bool(ul > ur),
+        Punct::LtEq if unsigned => Value::from_bool(ul <= ur),
+        Punct::GtEq if unsigned => Value::from_bool(ul >= ur),
+        Punct::Lt => Value::from_bool(l < r),
+        Punct::Gt => Value::from_bool(l > r),
+        Punct::LtEq => Value::from_bool(l <= r),
+        Punct::GtEq => Value::from_bool(l >= r),
+        Punct::LtLt => shifted(l.wrapping_shl(r as u32)),
+        Punct::GtGt if lhs.unsigned => shifted(ul.wrapping_shr(r as u32) as i64),
+        Punct::GtGt => shifted(l.wrapping_shr(r as u32)),
+        Punct::Plus => arithmetic(l.wrapping_add(r)),
+        Punct::Minus => arithmetic(l.wrapping_sub(r)),
+        Punct::Star => arithmetic(l.wrapping_mul(r)),
+        Punct::Slash | Punct::Percent if r == 0 => {
+            return Err(ParseError::DivisionByZeroInCondition)
+        }
+        Punct::Slash if unsigned => arithmetic((ul / ur) as i64),
+        Punct::Percent if unsigned => arithmetic((ul % ur) as i64),
+        Punct::Slash => arithmetic(l.wrapping_div(r)),
+        Punct::Percent => arithmetic(l.wrapping_rem(r)),
+        _ => unreachable!("not a binary operator"),
+    })
+}
+
+// a character constant is unsigned in #if if its type promotes to `unsigned
+// int`: a `char32_t`, or a 32-bit `wchar_t` that is unsigned.
+fn char_value(spelling: &[u8], target: &Target) -> Result<Value, ParseError> {
+    let spelling = spelling.as_bstr();
+    let invalid = |e| ParseError::InvalidCharConstant(spelling.to_owned(), e);
+    let literal = literal::decode(spelling, target).ok_or_else(|| invalid(CharError::NotChar))?;
+    let unsigned = match literal.encoding {
+        Encoding::Utf32 => true,
+        Encoding::Wide => target.wchar() == (32, false),
+        Encoding::Ordinary | Encoding::Utf8 | Encoding::Utf16 => false,
+    };
+    Ok(Value {
+        bits: literal.char_value(target).map_err(invalid)?,
+        unsigned,
+    })
+}
+
+// signed types act as `intmax_t` and unsigned ones as `uintmax_t`.
+fn parse_integer(spelling: &BStr, target: &Target) -> Result<Value, ParseError> {
+    let invalid = |e| ParseError::InvalidIntegerInCondition(spelling.to_owned(), e);
+    let integer = number::decode_integer(spelling, target).map_err(invalid)?;
+    let unsigned = matches!(
+        integer.ty,
+        IntegerType::UnsignedInt
+            | IntegerType::UnsignedLong
+            | IntegerType::UnsignedLongLong
+            | IntegerType::UnsignedBitInt(_)
+    );
+    let max = if unsigned {
+        u64::MAX.into()
+    } else {
+        i64::MAX as u128
+    };
+    if integer.value > max {
+        // only a `_BitInt` can be wider than `intmax_t`
+        return Err(invalid(IntegerError::TooLarge));
+    }
+    Ok(Value {
+        bits: integer.value as i64,
+        unsigned,
+    })
+}
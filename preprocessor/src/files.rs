@@ -0,0 +1,27 @@
+use bstr::BStr;
+use bstr::BString;
+
+// how the parser finds the headers a file includes. the library never
+// touches the filesystem itself, so it builds for wasm, where whoever embeds
+// it supplies the files.
+pub trait FileProvider {
+    // the path of the header `name` names when `from` includes it, with
+    // quotes if `quoted` or else angle brackets, if it can be found.
+    fn resolve(&self, name: &BStr, quoted: bool, from: &BStr) -> Option<BString>;
+
+    // the contents of a path `resolve` returned.
+    fn read(&self, path: &BStr) -> Option<Vec<u8>>;
+}
+
+// finds no headers, so every include is left unresolved.
+pub struct NoFiles;
+
+impl FileProvider for NoFiles {
+    fn resolve(&self, _name: &BStr, _quoted: bool, _from: &BStr) -> Option<BString> {
+        None
+    }
+
+    fn read(&self, _path: &BStr) -> Option<Vec<u8>> {
+        None
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use bstr::BStr;
+
+use crate::diagnostic::Diagnostic;
+use crate::lexer::lex_with_options;
+use crate::options::LangOptions;
+use crate::options::OutputOptions;
+use crate::parser::Parser;
+use crate::token::Token;
+use crate::warning::WarningOptions;
+
+// everything that affects what a golden test produces.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub lang: LangOptions,
+    pub output: OutputOptions,
+    pub warnings: WarningOptions,
+}
+
+// a golden test whose output differs from its expectation.
+#[derive(Debug)]
+pub struct Mismatch {
+    pub path: PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+// preprocess `source` and render the tokens, errors and warnings one per
+// line.
+pub fn render(source: &BStr, config: &Config) -> String {
+    let text = initial::fused::process(source);
+    let tokens = lex_with_options(text.as_ref(), config.lang.clone());
+    let mut parser = Parser::new(tokens, config.lang.clone())
+        .with_text(text.as_ref())
+        .with_output_options(config.output.clone())
+        .with_warning_options(config.warnings.clone());
+    let mut out = String::new();
+    while let Some(result) = parser.next() {
+        match &result {
+            Ok(tok) => writeln!(out, "{tok}").unwrap(),
+            Err(e) => writeln!(out, "{}", Diagnostic::from(e)).unwrap(),
+        }
+        for warning in parser.take_warnings() {
+            writeln!(out, "{}", warning).unwrap();
+        }
+        if let Ok(Token::Eof) = result {
+            break;
+        }
+    }
+    out
+}
+
+// render every `*.c` file in `dir` and compare it with the `.expected` file
+// next to it. with `bless`, expectations are (re)written instead.
+pub fn check_dir(dir: &Path, config: &Config, bless: bool) -> io::Result<Vec<Mismatch>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "c"));
+    paths.sort();
+
+    let mut mismatches = vec![];
+    for path in paths {
+        let source = fs::read(&path)?;
+        let actual = render(BStr::new(&source), config);
+        let expected_path = path.with_extension("expected");
+        if bless {
+            fs::write(&expected_path, &actual)?;
+            continue;
+        }
+        let expected = match fs::read_to_string(&expected_path) {
+            Ok(expected) => expected,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        if expected != actual {
+            mismatches.push(Mismatch {
+                path,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", self.path.display())?;
+        let expected = self.expected.lines().collect::<Vec<_>>();
+        let actual = self.actual.lines().collect::<Vec<_>>();
+        for i in 0..expected.len().max(actual.len()) {
+            match (expected.get(i), actual.get(i)) {
+                (Some(e), Some(a)) if e == a => writeln!(f, "  {e}")?,
+                (e, a) => {
+                    if let Some(e) = e {
+                        writeln!(f, "- {e}")?;
+                    }
+                    if let Some(a) = a {
+                        writeln!(f, "+ {a}")?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
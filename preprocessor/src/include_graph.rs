@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+
+use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice;
+use serde_json::json;
+
+use crate::files::FileProvider;
+use crate::lexer::lex_with_options;
+use crate::options::LangOptions;
+use crate::parser::Parser;
+
+// who included whom. a header is named by its path if the parser's
+// `FileProvider` found it, and by its name as written if not.
+#[derive(Debug, Clone, Default)]
+pub struct IncludeGraph {
+    files: Vec<BString>,
+    pub edges: Vec<Inclusion>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Inclusion {
+    // indices into `IncludeGraph::files`
+    pub from: usize,
+    pub to: usize,
+    pub line: u32,
+    // whether `to` is a path the header was found at
+    pub resolved: bool,
+    // the header name as written, with its quotes or angle brackets
+    pub spelling: BString,
+}
+
+impl IncludeGraph {
+    pub fn files(&self) -> &[BString] {
+        &self.files
+    }
+
+    fn file_id(&mut self, name: &BStr) -> usize {
+        match self.files.iter().position(|file| file == name) {
+            Some(id) => id,
+            None => {
+                self.files.push(name.to_owned());
+                self.files.len() - 1
+            }
+        }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        from: &BStr,
+        to: &BStr,
+        resolved: bool,
+        line: u32,
+        spelling: &BStr,
+    ) {
+        let from = self.file_id(from);
+        let to = self.file_id(to);
+        self.edges.push(Inclusion {
+            from,
+            to,
+            resolved,
+            line,
+            spelling: spelling.to_owned(),
+        });
+    }
+
+    // add what each header found includes, and so on. a header is
+    // preprocessed on its own, so macros don't carry over, and followed only
+    // the first time it is reached, as though it had an include guard.
+    pub(crate) fn follow(&mut self, files: &dyn FileProvider, options: &LangOptions) {
+        let mut followed: HashSet<usize> = self.edges.iter().map(|edge| edge.from).collect();
+        let mut next = 0;
+        while let Some(edge) = self.edges.get(next) {
+            next += 1;
+            if !edge.resolved || !followed.insert(edge.to) {
+                continue;
+            }
+            let path = self.files[edge.to].clone();
+            let Some(text) = files.read(path.as_ref()) else {
+                continue;
+            };
+            let text = initial::fused::process(text.as_bstr());
+            let tokens = lex_with_options(text.as_ref(), options.clone());
+            let mut parser = Parser::new(tokens, options.clone())
+                .with_text(text.as_ref())
+                .with_file_name(path.clone());
+            parser.by_ref().for_each(drop);
+            for inclusion in &parser.include_graph().edges {
+                let spelling = inclusion.spelling.as_bstr();
+                let name = spelling[1..spelling.len() - 1].as_bstr();
+                let resolved = files.resolve(name, spelling[0] == b'"', path.as_ref());
+                let to = resolved.as_ref().map_or(name, |path| path.as_bstr());
+                self.record(
+                    path.as_ref(),
+                    to,
+                    resolved.is_some(),
+                    inclusion.line,
+                    spelling,
+                );
+            }
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph includes {\n");
+        for (id, file) in self.files.iter().enumerate() {
+            writeln!(out, "    n{id} [label={}];", dot_string(file.as_ref())).unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(
+                out,
+                "    n{} -> n{} [label=\"line {}\"];",
+                edge.from, edge.to, edge.line
+            )
+            .unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let files = self
+            .files
+            .iter()
+            .map(|file| file.to_string())
+            .collect::<Vec<_>>();
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| {
+                json!({
+                    "from": edge.from,
+                    "to": edge.to,
+                    "line": edge.line,
+                    "resolved": edge.resolved,
+                    "spelling": edge.spelling.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({ "files": files, "edges": edges }).to_string()
+    }
+}
+
+// `text` as a quoted DOT string. a backslash starts an escape in labels, so
+// it is doubled along with quotes, and line breaks become `\n`.
+fn dot_string(text: &BStr) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,152 @@
+use std::ops::Range;
+
+use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice;
+
+use crate::lexer::lex_with_options;
+use crate::options::LangOptions;
+use crate::token::Token;
+
+// a source buffer that keeps its phase 1-3 text and tokens up to date across
+// edits, redoing only the lines an edit can affect.
+pub struct Document {
+    options: LangOptions,
+    source: Vec<u8>,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EditError {
+    #[error("edit range {start}..{end} doesn't fit in a source of {len} bytes")]
+    InvalidRange {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+}
+
+// a run of lines that phases 1-3 and the lexer can handle on their own.
+struct Chunk {
+    source_len: usize,
+    text: BString,
+    // token text is stored as a range into `text`
+    tokens: Vec<(Token<'static>, Range<usize>)>,
+}
+
+impl Document {
+    pub fn new(source: &BStr, options: LangOptions) -> Self {
+        let mut doc = Self {
+            options,
+            source: source.to_vec(),
+            chunks: vec![],
+        };
+        doc.chunks = doc.make_chunks(0, &[]);
+        doc
+    }
+
+    pub fn source(&self) -> &BStr {
+        BStr::new(&self.source)
+    }
+
+    // the output of phases 1-3.
+    pub fn text(&self) -> BString {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| chunk.text.iter().copied())
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = Token<'_>> {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| {
+                chunk
+                    .tokens
+                    .iter()
+                    .map(|(tok, range)| tok.map_text(|_| chunk.text[range.clone()].as_bstr()))
+            })
+            .chain(std::iter::once(Token::Eof))
+    }
+
+    // replace `range` of the source with `replacement`.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &[u8]) -> Result<(), EditError> {
+        if range.start > range.end || range.end > self.source.len() {
+            return Err(EditError::InvalidRange {
+                start: range.start,
+                end: range.end,
+                len: self.source.len(),
+            });
+        }
+        let mut first = 0;
+        let mut first_start = 0;
+        while first + 1 < self.chunks.len()
+            && first_start + self.chunks[first].source_len <= range.start
+        {
+            first_start += self.chunks[first].source_len;
+            first += 1;
+        }
+        // old chunk boundaries past the edit, in new coordinates, with the
+        // index of the chunk starting there
+        let mut boundaries = vec![];
+        let mut end = first_start;
+        for (i, chunk) in self.chunks.iter().enumerate().skip(first) {
+            end += chunk.source_len;
+            if end > range.end {
+                boundaries.push((end - range.len() + replacement.len(), i + 1));
+            }
+        }
+
+        self.source.splice(range, replacement.iter().copied());
+        let chunks = self.make_chunks(first_start, &boundaries);
+        let resume = chunks.iter().map(|chunk| chunk.source_len).sum::<usize>() + first_start;
+        let last = boundaries
+            .iter()
+            .find(|&&(boundary, _)| boundary == resume)
+            .map_or(self.chunks.len(), |&(_, i)| i);
+        self.chunks.splice(first..last, chunks);
+        Ok(())
+    }
+
+    // split the source from `start` into chunks, stopping early at one of
+    // `boundaries` where the old chunks can be reused.
+    fn make_chunks(&self, start: usize, boundaries: &[(usize, usize)]) -> Vec<Chunk> {
+        let rest = BStr::new(&self.source[start..]);
+        let mut chunks = vec![];
+        let mut prev = 0;
+        for point in initial::fused::split_points(rest) {
+            chunks.push(self.make_chunk(&rest[prev..point]));
+            prev = point;
+            let reusable = boundaries
+                .iter()
+                .any(|&(boundary, _)| boundary == start + point);
+            if reusable {
+                return chunks;
+            }
+        }
+        if prev < rest.len() {
+            chunks.push(self.make_chunk(&rest[prev..]));
+        }
+        chunks
+    }
+
+    fn make_chunk(&self, source: &[u8]) -> Chunk {
+        let text = initial::fused::process(BStr::new(source));
+        let tokens = lex_with_options(text.as_ref(), self.options.clone())
+            .filter(|tok| !matches!(tok, Token::Eof))
+            .map(|tok| {
+                let range = match tok.offset_in(text.as_ref()) {
+                    Some(start) => start..start + tok.spelling().len(),
+                    None => 0..0,
+                };
+                (tok.map_text(|_| BStr::new("")), range)
+            })
+            .collect();
+        Chunk {
+            source_len: source.len(),
+            text,
+            tokens,
+        }
+    }
+}
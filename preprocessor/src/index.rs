@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use bstr::BStr;
+use bstr::BString;
+
+// where each macro was defined and referenced, for "go to definition" and
+// "find all references".
+#[derive(Debug, Clone, Default)]
+pub struct MacroIndex {
+    macros: HashMap<BString, MacroEntry>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MacroEntry {
+    pub definitions: Vec<Site>,
+    // expansion sites, plus `#undef`s of the macro
+    pub uses: Vec<Site>,
+}
+
+// a presumed location, as adjusted by `#line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Site {
+    pub file: Option<BString>,
+    pub line: u32,
+}
+
+impl MacroIndex {
+    pub(crate) fn record_definition(&mut self, name: &BStr, site: Site) {
+        self.entry(name).definitions.push(site);
+    }
+
+    pub(crate) fn record_use(&mut self, name: &BStr, site: Site) {
+        self.entry(name).uses.push(site);
+    }
+
+    fn entry(&mut self, name: &BStr) -> &mut MacroEntry {
+        self.macros.entry(name.to_owned()).or_default()
+    }
+
+    pub fn get(&self, name: &BStr) -> Option<&MacroEntry> {
+        self.macros.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&BStr, &MacroEntry)> {
+        self.macros
+            .iter()
+            .map(|(name, entry)| (name.as_ref(), entry))
+    }
+}
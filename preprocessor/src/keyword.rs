@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+
+use bstr::BStr;
+use bstr::ByteSlice;
+
+use crate::options::CStandard;
+use crate::options::CxxStandard;
+use crate::options::LangOptions;
+use crate::token::Token;
+
+// each standard's additions. C23's lowercase spellings of `_Bool`,
+// `_Static_assert` and the rest don't retire the old ones.
+const C_KEYWORDS: &[(CStandard, &[&str])] = &[
+    (
+        CStandard::C89,
+        &[
+            "auto", "break", "case", "char", "const", "continue", "default", "do", "double",
+            "else", "enum", "extern", "float", "for", "goto", "if", "int", "long", "register",
+            "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+            "union", "unsigned", "void", "volatile", "while",
+        ],
+    ),
+    (
+        CStandard::C99,
+        &["_Bool", "_Complex", "_Imaginary", "inline", "restrict"],
+    ),
+    (
+        CStandard::C11,
+        &[
+            "_Alignas",
+            "_Alignof",
+            "_Atomic",
+            "_Generic",
+            "_Noreturn",
+            "_Static_assert",
+            "_Thread_local",
+        ],
+    ),
+    (
+        CStandard::C23,
+        &[
+            "_BitInt",
+            "_Decimal128",
+            "_Decimal32",
+            "_Decimal64",
+            "alignas",
+            "alignof",
+            "bool",
+            "constexpr",
+            "false",
+            "nullptr",
+            "static_assert",
+            "thread_local",
+            "true",
+            "typeof",
+            "typeof_unqual",
+        ],
+    ),
+];
+
+const CXX_KEYWORDS: &[(CxxStandard, &[&str])] = &[
+    (
+        CxxStandard::Cxx98,
+        &[
+            "asm",
+            "auto",
+            "bool",
+            "break",
+            "case",
+            "catch",
+            "char",
+            "class",
+            "const",
+            "const_cast",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "double",
+            "dynamic_cast",
+            "else",
+            "enum",
+            "explicit",
+            "export",
+            "extern",
+            "false",
+            "float",
+            "for",
+            "friend",
+            "goto",
+            "if",
+            "inline",
+            "int",
+            "long",
+            "mutable",
+            "namespace",
+            "new",
+            "operator",
+            "private",
+            "protected",
+            "public",
+            "register",
+            "reinterpret_cast",
+            "return",
+            "short",
+            "signed",
+            "sizeof",
+            "static",
+            "static_cast",
+            "struct",
+            "switch",
+            "template",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "typedef",
+            "typeid",
+            "typename",
+            "union",
+            "unsigned",
+            "using",
+            "virtual",
+            "void",
+            "volatile",
+            "wchar_t",
+            "while",
+        ],
+    ),
+    (
+        CxxStandard::Cxx11,
+        &[
+            "alignas",
+            "alignof",
+            "char16_t",
+            "char32_t",
+            "constexpr",
+            "decltype",
+            "noexcept",
+            "nullptr",
+            "static_assert",
+            "thread_local",
+        ],
+    ),
+    (
+        CxxStandard::Cxx20,
+        &[
+            "char8_t",
+            "co_await",
+            "co_return",
+            "co_yield",
+            "concept",
+            "consteval",
+            "constinit",
+            "requires",
+        ],
+    ),
+];
+
+// the keywords of the language and standard `options` select.
+pub fn keywords(options: &LangOptions) -> impl Iterator<Item = &'static str> {
+    let tables: Vec<&[&str]> = match options.cplusplus {
+        Some(std) => CXX_KEYWORDS
+            .iter()
+            .filter(|(since, _)| std >= *since)
+            .map(|(_, table)| *table)
+            .collect(),
+        None => C_KEYWORDS
+            .iter()
+            .filter(|(since, _)| options.c_standard >= *since)
+            .map(|(_, table)| *table)
+            .collect(),
+    };
+    tables.into_iter().flatten().copied()
+}
+
+// whether the phase-7 conversion of `Ident(name)` is a keyword.
+pub fn is_keyword(name: &BStr, options: &LangOptions) -> bool {
+    keywords(options).any(|keyword| name == keyword)
+}
+
+// the phase-7 conversion of identifiers into keywords, over the
+// preprocessor's output. errors are passed along where they occur.
+pub struct Keywords<Tokens> {
+    tokens: Tokens,
+    keywords: HashSet<&'static str>,
+}
+
+impl<Tokens> Keywords<Tokens> {
+    pub fn new(tokens: Tokens, options: &LangOptions) -> Self {
+        Self {
+            tokens,
+            keywords: keywords(options).collect(),
+        }
+    }
+}
+
+impl<'a, E, Tokens> Iterator for Keywords<Tokens>
+where
+    Tokens: Iterator<Item = Result<Token<'a>, E>>,
+{
+    type Item = Result<Token<'a>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tok = self.tokens.next()?;
+        Some(tok.map(|tok| match tok {
+            Token::Ident(name) if name.to_str().is_ok_and(|name| self.keywords.contains(name)) => {
+                Token::Keyword(name)
+            }
+            tok => tok,
+        }))
+    }
+}
@@ -1,25 +1,58 @@
 use bstr::BStr;
+use bstr::ByteSlice;
 
+use crate::options::CxxStandard;
+use crate::options::LangOptions;
 use crate::token::Punct;
 use crate::token::Token;
 
 struct Lexer<'a> {
     input: &'a BStr,
+    options: LangOptions,
     pos: usize,
     at_line_start: bool,
     in_directive: bool,
+    // the last token was the `#` introducing a directive.
+    after_directive_hash: bool,
+    // the line so far is just `export`, which may start a module directive.
+    after_export: bool,
+    in_include: bool,
+    // the last token was `__has_include` or `__has_include_next` in a
+    // directive, whose `(` opens a header-name operand.
+    after_has_include: bool,
+    // the last token was that `(`.
+    in_has_include: bool,
+    // whitespace was skipped before the current token.
+    space_before: bool,
 }
 
 impl<'a> Lexer<'a> {
-    fn new(input: &'a BStr) -> Self {
+    fn new(input: &'a BStr, options: LangOptions) -> Self {
         Self {
             input,
+            options,
             pos: 0,
             at_line_start: true,
             in_directive: false,
+            after_directive_hash: false,
+            after_export: false,
+            in_include: false,
+            after_has_include: false,
+            in_has_include: false,
+            space_before: false,
         }
     }
 
+    fn cplusplus(&self) -> bool {
+        self.options.cplusplus.is_some()
+    }
+
+    // header names are only recognized where one is expected, so `<` in
+    // `#if 1 < 2 || 3 > 4` stays an operator.
+    fn header_name_allowed(&self) -> bool {
+        self.in_include || self.in_has_include
+    }
+
     fn get(&self) -> Option<u8> {
         self.input.get(self.pos).copied()
     }
@@ -30,11 +63,37 @@ impl<'a> Lexer<'a> {
         if let Some(b'\n') = c {
             self.at_line_start = true;
             self.in_directive = false;
+            self.after_directive_hash = false;
+            self.after_export = false;
+            self.in_include = false;
+            self.after_has_include = false;
+            self.in_has_include = false;
         }
     }
 
     fn end_token(&mut self, k: Token<'a>) -> Token<'a> {
-        if self.at_line_start && k.is_hash() {
+        if self.after_directive_hash {
+            if let Token::Ident(name) = k {
+                self.in_include = matches!(
+                    name.as_bytes(),
+                    b"include" | b"include_next" | b"import" | b"embed"
+                );
+            }
+        }
+        self.in_has_include = self.after_has_include && k == Token::Punct(Punct::LParen);
+        self.after_has_include = self.in_directive
+            && matches!(k, Token::Ident(name) if name == "__has_include" || name == "__has_include_next");
+        if self.options.cplusplus >= Some(CxxStandard::Cxx20) {
+            if let Token::Ident(name) = k {
+                let line_start = self.at_line_start || self.after_export;
+                self.in_include |= line_start && name == "import";
+                self.after_export = self.at_line_start && name == "export";
+            } else {
+                self.after_export = false;
+            }
+        }
+        self.after_directive_hash = self.at_line_start && k.is_hash();
+        if self.after_directive_hash {
             self.in_directive = true;
         }
 
@@ -43,13 +102,18 @@ impl<'a> Lexer<'a> {
     }
 
     fn peek(&self) -> Option<u8> {
-        self.input.get(self.pos + 1).copied()
+        self.peek_nth(1)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<u8> {
+        self.input.get(self.pos + n).copied()
     }
 
     fn skip_whitespace(&mut self) -> Option<Token<'a>> {
         loop {
             match self.get() {
                 Some(b' ' | b'\t' | b'\r') => {
+                    self.space_before = true;
                     self.move_on();
                 }
                 Some(b'\n') => {
@@ -61,22 +125,73 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    // the length of the UTF-8 character at `self.pos` if `accept` allows it.
+    fn extended_char(&self, accept: fn(char) -> bool) -> Option<usize> {
+        match bstr::decode_utf8(&self.input[self.pos..]) {
+            (Some(c), len) if accept(c) => Some(len),
+            _ => None,
+        }
+    }
+
     fn scan_ident(&mut self) -> Token<'a> {
         let start = self.pos;
         loop {
             match self.get() {
-                Some(b'a'..=b'z' | b'A'..=b'Z' | b'_') => {
+                Some(b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'0'..=b'9') => {
                     self.move_on();
                 }
+                Some(b'$') if self.options.gnu_extensions => self.move_on(),
+                Some(0x80..) => match self.extended_char(unicode_ident::is_xid_continue) {
+                    Some(len) => self.pos += len,
+                    None => break,
+                },
                 _ => break,
             }
         }
         let end = self.pos;
-        self.end_token(Token::Ident(&self.input[start..end]))
+        let ident = &self.input[start..end];
+        if self.cplusplus() {
+            if self.get() == Some(b'"')
+                && self.options.cplusplus >= Some(CxxStandard::Cxx11)
+                && matches!(ident.as_bytes(), b"R" | b"u8R" | b"uR" | b"UR" | b"LR")
+            {
+                if let Some(tok) = self.scan_raw_string(start) {
+                    return tok;
+                }
+            }
+            if let Some(p) = alternative_token(ident) {
+                return self.end_token(Token::Punct(p));
+            }
+        }
+        self.end_token(Token::Ident(ident))
     }
 
-    fn scan_number(&mut self) -> Option<Token<'a>> {
-        let first = self.get().unwrap();
+    // `R"delim( ... )delim"`, with `self.pos` on the opening quote.
+    fn scan_raw_string(&mut self, start: usize) -> Option<Token<'a>> {
+        let quote = self.pos;
+        let delim_start = quote + 1;
+        let delim_len = self.input[delim_start..].iter().position(|&c| c == b'(')?;
+        let delim = &self.input[delim_start..delim_start + delim_len];
+        if delim_len > 16
+            || delim
+                .iter()
+                .any(|&c| matches!(c, b' ' | b'\t' | b'\n' | b'\r' | b')' | b'\\'))
+        {
+            return None;
+        }
+
+        let body_start = delim_start + delim_len + 1;
+        let body_len = self.input[body_start..]
+            .windows(delim_len + 2)
+            .position(|w| w[0] == b')' && &w[1..=delim_len] == delim && w[delim_len + 1] == b'"')?;
+        let end = body_start + body_len + delim_len + 2;
+        while self.pos < end {
+            self.move_on();
+        }
+        Some(self.end_token(Token::StringLit(&self.input[start..end])))
+    }
+
+    fn scan_number(&mut self, first: u8) -> Option<Token<'a>> {
         let start = self.pos;
         self.move_on();
         if first == b'.' {
@@ -121,17 +236,11 @@ impl<'a> Lexer<'a> {
         Some(self.end_token(Token::Number(&self.input[start..end])))
     }
 
-    fn scan_string_lit(&mut self) -> Option<Token<'a>> {
-        let first = self.get().unwrap();
-        let terminator = match first {
-            b'"' => b'"',
-            b'\'' => b'\'',
-            b'<' => b'>',
-            _ => unreachable!("string literal starts with wrong char"),
-        };
+    fn scan_string_lit(&mut self, first: u8) -> Option<Token<'a>> {
+        let terminator = if first == b'<' { b'>' } else { first };
         let start = self.pos;
         self.move_on();
-        if let Some(b':' | b'%') = self.get() {
+        if first == b'<' && matches!(self.get(), Some(b':' | b'%')) {
             self.pos = start;
             return None;
         }
@@ -145,10 +254,13 @@ impl<'a> Lexer<'a> {
                 }
                 Some(b'\\') if first != b'<' => {
                     self.move_on();
-                    self.move_on();
+                    // a backslash at the end of the input escapes nothing
+                    if self.get().is_some() {
+                        self.move_on();
+                    }
                 }
-                Some(b'\n') => break,
-                _ => {
+                Some(b'\n') | None => break,
+                Some(_) => {
                     self.move_on();
                 }
             }
@@ -161,49 +273,53 @@ impl<'a> Lexer<'a> {
         Some(self.end_token(Token::Other(&self.input[start..end])))
     }
 
-    fn scan_punct(&mut self) -> Token<'a> {
-        let first = self.get().unwrap();
+    fn scan_punct(&mut self, first: u8) -> Token<'a> {
+        let start = self.pos;
         self.move_on();
 
         // check digraphs
-        match first {
-            b'<' => match self.get() {
-                Some(b':') => {
-                    self.move_on();
-                    return self.end_token(Token::Punct(Punct::LBrack));
-                }
-                Some(b'%') => {
-                    self.move_on();
-                    return self.end_token(Token::Punct(Punct::LBrace));
-                }
-                _ => {}
-            },
-            b'%' => match self.get() {
-                Some(b'>') => {
-                    self.move_on();
-                    return self.end_token(Token::Punct(Punct::RBrace));
-                }
-                Some(b':') => {
-                    self.move_on();
-                    match self.get() {
-                        Some(b'%') if self.peek() == Some(b':') => {
+        if self.options.digraphs {
+            match first {
+                b'<' => match self.get() {
+                    // in C++, `<::` is `<` `::` unless followed by `:` or `>`
+                    Some(b':')
+                        if self.cplusplus()
+                            && self.peek() == Some(b':')
+                            && !matches!(self.peek_nth(2), Some(b':' | b'>')) => {}
+                    Some(b':') => {
+                        self.move_on();
+                        return self.end_token(Token::Punct(Punct::LBrack));
+                    }
+                    Some(b'%') => {
+                        self.move_on();
+                        return self.end_token(Token::Punct(Punct::LBrace));
+                    }
+                    _ => {}
+                },
+                b'%' => match self.get() {
+                    Some(b'>') => {
+                        self.move_on();
+                        return self.end_token(Token::Punct(Punct::RBrace));
+                    }
+                    Some(b':') => {
+                        self.move_on();
+                        if self.get() == Some(b'%') && self.peek() == Some(b':') {
                             self.move_on();
                             self.move_on();
                             return self.end_token(Token::Punct(Punct::HashHash));
                         }
-                        _ => {}
+                        return self.end_token(Token::Punct(Punct::Hash));
+                    }
+                    _ => {}
+                },
+                b':' => {
+                    if let Some(b'>') = self.get() {
+                        self.move_on();
+                        return self.end_token(Token::Punct(Punct::RBrack));
                     }
                 }
                 _ => {}
-            },
-            b':' => match self.get() {
-                Some(b'>') => {
-                    self.move_on();
-                    return self.end_token(Token::Punct(Punct::RBrack));
-                }
-                _ => {}
-            },
-            _ => {}
+            }
         }
 
         // not a digraph
@@ -217,6 +333,9 @@ impl<'a> Lexer<'a> {
                     self.move_on();
                     self.move_on();
                     self.end_token(Token::Punct(Punct::Ellipsis))
+                } else if self.cplusplus() && self.get() == Some(b'*') {
+                    self.move_on();
+                    self.end_token(Token::Punct(Punct::PeriodStar))
                 } else {
                     self.end_token(Token::Punct(Punct::Period))
                 }
@@ -224,7 +343,12 @@ impl<'a> Lexer<'a> {
             b'-' => match self.get() {
                 Some(b'>') => {
                     self.move_on();
-                    self.end_token(Token::Punct(Punct::Arrow))
+                    if self.cplusplus() && self.get() == Some(b'*') {
+                        self.move_on();
+                        self.end_token(Token::Punct(Punct::ArrowStar))
+                    } else {
+                        self.end_token(Token::Punct(Punct::Arrow))
+                    }
                 }
                 Some(b'-') => {
                     self.move_on();
@@ -294,7 +418,14 @@ impl<'a> Lexer<'a> {
             b'<' => match self.get() {
                 Some(b'=') => {
                     self.move_on();
-                    self.end_token(Token::Punct(Punct::LtEq))
+                    if self.options.cplusplus >= Some(CxxStandard::Cxx20)
+                        && self.get() == Some(b'>')
+                    {
+                        self.move_on();
+                        self.end_token(Token::Punct(Punct::Spaceship))
+                    } else {
+                        self.end_token(Token::Punct(Punct::LtEq))
+                    }
                 }
                 Some(b'<') => {
                     self.move_on();
@@ -351,7 +482,14 @@ impl<'a> Lexer<'a> {
                 _ => self.end_token(Token::Punct(Punct::Pipe)),
             },
             b'?' => self.end_token(Token::Punct(Punct::Question)),
-            b':' => self.end_token(Token::Punct(Punct::Colon)),
+            b':' => {
+                if self.cplusplus() && self.get() == Some(b':') {
+                    self.move_on();
+                    self.end_token(Token::Punct(Punct::ColonColon))
+                } else {
+                    self.end_token(Token::Punct(Punct::Colon))
+                }
+            }
             b',' => self.end_token(Token::Punct(Punct::Comma)),
             b'#' => {
                 if self.get() == Some(b'#') {
@@ -364,7 +502,7 @@ impl<'a> Lexer<'a> {
             b'{' => self.end_token(Token::Punct(Punct::LBrace)),
             b'}' => self.end_token(Token::Punct(Punct::RBrace)),
             b';' => self.end_token(Token::Punct(Punct::Semicolon)),
-            _ => unreachable!("impossible punctuation"),
+            _ => self.end_token(Token::Other(&self.input[start..self.pos])),
         }
     }
 
@@ -379,40 +517,109 @@ impl<'a> Iterator for Lexer<'a> {
     type Item = Token<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.space_before = false;
         if let Some(t) = self.skip_whitespace() {
             return Some(t);
         }
 
         match self.get() {
             Some(b'a'..=b'z' | b'A'..=b'Z' | b'_') => Some(self.scan_ident()),
-            Some(b'0'..=b'9' | b'.') => {
-                Some(self.scan_number().unwrap_or_else(|| self.scan_punct()))
+            Some(b'$') if self.options.gnu_extensions => Some(self.scan_ident()),
+            Some(0x80..) if self.extended_char(unicode_ident::is_xid_start).is_some() => {
+                Some(self.scan_ident())
             }
-            Some(b'"' | b'\'' | b'<') => {
-                let result = if self.in_directive || self.get() != Some(b'<') {
-                    self.scan_string_lit()
+            Some(c @ (b'0'..=b'9' | b'.')) => {
+                Some(self.scan_number(c).unwrap_or_else(|| self.scan_punct(c)))
+            }
+            Some(c @ (b'"' | b'\'' | b'<')) => {
+                let result = if self.header_name_allowed() || c != b'<' {
+                    self.scan_string_lit(c)
                 } else {
                     None
                 };
-                Some(result.unwrap_or_else(|| self.scan_punct()))
+                Some(result.unwrap_or_else(|| self.scan_punct(c)))
             }
             Some(
-                b'!'
+                c @ (b'!'
                 | b'#'
                 | b'%'..=b'&'
                 | b'('..=b'-'
                 | b'/'
                 | b':'..=b';'
                 | b'='..=b'?'
-                | b'['..=b'^'
-                | b'{'..=b'~',
-            ) => Some(self.scan_punct()),
+                // a lone `\` is no punctuator
+                | b'['
+                | b']'..=b'^'
+                | b'{'..=b'~'),
+            ) => Some(self.scan_punct(c)),
             Some(_) => Some(self.scan_other()),
             _ => None,
         }
     }
 }
 
+fn alternative_token(ident: &BStr) -> Option<Punct> {
+    match ident.as_bytes() {
+        b"and" => Some(Punct::AmpAmp),
+        b"and_eq" => Some(Punct::AmpEq),
+        b"bitand" => Some(Punct::Amp),
+        b"bitor" => Some(Punct::Pipe),
+        b"compl" => Some(Punct::Tilde),
+        b"not" => Some(Punct::Bang),
+        b"not_eq" => Some(Punct::BangEq),
+        b"or" => Some(Punct::PipePipe),
+        b"or_eq" => Some(Punct::PipeEq),
+        b"xor" => Some(Punct::Caret),
+        b"xor_eq" => Some(Punct::CaretEq),
+        _ => None,
+    }
+}
+
 pub fn lex<'a>(input: &'a BStr) -> impl Iterator<Item = Token<'a>> {
-    Lexer::new(input).chain(std::iter::once(Token::Eof))
+    lex_with_options(input, LangOptions::default())
+}
+
+pub fn lex_with_options<'a>(
+    input: &'a BStr,
+    options: LangOptions,
+) -> impl Iterator<Item = Token<'a>> {
+    Lexer::new(input, options).chain(std::iter::once(Token::Eof))
+}
+
+// like `lex_with_options`, but also says whether each token was preceded by
+// whitespace on its line.
+pub fn lex_with_spacing<'a>(
+    input: &'a BStr,
+    options: LangOptions,
+) -> impl Iterator<Item = (Token<'a>, bool)> {
+    let mut lexer = Lexer::new(input, options);
+    std::iter::from_fn(move || lexer.next().map(|tok| (tok, lexer.space_before)))
+        .chain(std::iter::once((Token::Eof, false)))
+}
+
+// what the parser reads: a token, and whether whitespace came before it if
+// the lexer said so.
+pub trait Lexed<'a>: Copy {
+    fn token(self) -> Token<'a>;
+    fn space_before(self) -> bool;
+}
+
+impl<'a> Lexed<'a> for Token<'a> {
+    fn token(self) -> Token<'a> {
+        self
+    }
+
+    fn space_before(self) -> bool {
+        false
+    }
+}
+
+impl<'a> Lexed<'a> for (Token<'a>, bool) {
+    fn token(self) -> Token<'a> {
+        self.0
+    }
+
+    fn space_before(self) -> bool {
+        self.1
+    }
 }
@@ -1,3 +1,26 @@
+pub mod callbacks;
+pub mod coverage;
+pub mod cursor;
+pub mod definition;
+pub mod diagnostic;
+mod expr;
+pub mod files;
+pub mod golden;
+pub mod include_graph;
+pub mod incremental;
+pub mod index;
+pub mod keyword;
 pub mod lexer;
+pub mod limits;
+pub mod literal;
+pub mod normalize;
+pub mod number;
+pub mod options;
+pub mod output;
 pub mod parser;
+pub mod pragma;
+pub mod semantic;
+pub mod stats;
+pub mod target;
 pub mod token;
+pub mod warning;
@@ -0,0 +1,34 @@
+use crate::options::LangOptions;
+
+// the least every conforming implementation must accept, from C17 5.2.4.1
+// and [implimits] in C++. source that goes past them may not be portable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslationLimits {
+    pub conditional_nesting: usize,
+    pub macro_parameters: usize,
+    pub macro_arguments: usize,
+    pub logical_line_length: usize,
+    pub macro_definitions: usize,
+}
+
+impl TranslationLimits {
+    pub fn for_options(options: &LangOptions) -> Self {
+        if options.cplusplus.is_some() {
+            Self {
+                conditional_nesting: 256,
+                macro_parameters: 256,
+                macro_arguments: 256,
+                logical_line_length: 65536,
+                macro_definitions: 65536,
+            }
+        } else {
+            Self {
+                conditional_nesting: 63,
+                macro_parameters: 127,
+                macro_arguments: 127,
+                logical_line_length: 4095,
+                macro_definitions: 4095,
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::ops::Range;
+
+use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice;
+
+use crate::target::Target;
+
+// the encoding prefix of a character constant or string literal, which
+// decides the execution character set its contents are converted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    // no prefix: UTF-8 bytes
+    Ordinary,
+    // `u8`
+    Utf8,
+    // `u`: UTF-16 code units
+    Utf16,
+    // `U`: UTF-32 code units
+    Utf32,
+    // `L`: `wchar_t`, holding UTF-32, or UTF-16 where it is 16 bits
+    Wide,
+}
+
+// a literal's contents in the execution character set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Literal {
+    pub encoding: Encoding,
+    // `'` for a character constant, `"` for a string literal
+    pub delimiter: u8,
+    // code units as wide as the encoding's on the target, with no
+    // terminating null
+    pub units: Vec<u32>,
+    // malformed escapes, with the byte range each covers in the spelling
+    pub errors: Vec<(Range<usize>, EscapeError)>,
+}
+
+// each holds the escape as spelled.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EscapeError {
+    #[error("unknown escape sequence `{0}`")]
+    Unknown(BString),
+    #[error("`{0}` used with no following digits")]
+    MissingDigits(BString),
+    #[error("missing `}}` to end `{0}`")]
+    Unterminated(BString),
+    #[error("escape sequence `{0}` is out of range")]
+    OutOfRange(BString),
+    #[error("incomplete universal character name `{0}`")]
+    IncompleteUcn(BString),
+    #[error("`{0}` is not a valid universal character name")]
+    InvalidUcn(BString),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum CharError {
+    #[error("not a character constant")]
+    NotChar,
+    #[error("empty character constant")]
+    Empty,
+    #[error("character constant too long for its type")]
+    TooLong,
+}
+
+impl Encoding {
+    // split the encoding prefix off `spelling`. the lexer doesn't attach
+    // prefixes to literals, so callers that have one join it back on.
+    pub fn split(spelling: &BStr) -> (Self, &BStr) {
+        let prefixes = [
+            ("u8", Self::Utf8),
+            ("u", Self::Utf16),
+            ("U", Self::Utf32),
+            ("L", Self::Wide),
+        ];
+        for (prefix, encoding) in prefixes {
+            if let Some(rest) = spelling.strip_prefix(prefix.as_bytes()) {
+                return (encoding, rest.as_bstr());
+            }
+        }
+        (Self::Ordinary, spelling)
+    }
+
+    // the width of a code unit in bits on `target`.
+    pub fn unit_width(self, target: &Target) -> u32 {
+        match self {
+            Self::Ordinary | Self::Utf8 => 8,
+            Self::Utf16 => 16,
+            Self::Utf32 => 32,
+            Self::Wide => target.wchar().0,
+        }
+    }
+
+    // the largest value one code unit holds on `target`.
+    pub fn max_unit(self, target: &Target) -> u32 {
+        u32::MAX >> (32 - self.unit_width(target))
+    }
+}
+
+impl Literal {
+    // the value of a character constant, computed as GCC does for `target`,
+    // whose `char` is 8 bits and `int` 32. a plain constant has type `int`:
+    // one byte is extended as `char` is signed or not, and several are
+    // combined with the first in the most significant position, keeping the
+    // low 32 bits. with `L`, several characters are allowed and the last one
+    // is the value, as a `wchar_t`. `u8`, `u` and `U` constants are unsigned
+    // and must be a single code unit.
+    pub fn char_value(&self, target: &Target) -> Result<i64, CharError> {
+        if self.delimiter != b'\'' {
+            return Err(CharError::NotChar);
+        }
+        let (&last, rest) = self.units.split_last().ok_or(CharError::Empty)?;
+        match self.encoding {
+            Encoding::Ordinary if rest.is_empty() && target.char_signed() => {
+                Ok((last as u8 as i8).into())
+            }
+            Encoding::Ordinary if rest.is_empty() => Ok((last as u8).into()),
+            Encoding::Ordinary => {
+                let value = self
+                    .units
+                    .iter()
+                    .fold(0, |value: u32, &unit| value << 8 | unit);
+                Ok((value as i32).into())
+            }
+            Encoding::Wide => Ok(match target.wchar() {
+                (16, true) => (last as i16).into(),
+                (16, false) => (last as u16).into(),
+                (_, true) => (last as i32).into(),
+                (_, false) => last.into(),
+            }),
+            _ if !rest.is_empty() => Err(CharError::TooLong),
+            _ => Ok(last.into()),
+        }
+    }
+}
+
+// convert the spelling of a character constant or string literal, prefix
+// included, to the execution-character-set code units of `target`. `None`
+// if it isn't one.
+//
+// malformed escapes are reported in `errors`, and decoded as GCC does: an
+// unknown escape stands for the character after the `\`, numeric escapes too
+// big for a code unit keep only the bits that fit, and an invalid universal
+// character name becomes U+FFFD.
+pub fn decode(spelling: &BStr, target: &Target) -> Option<Literal> {
+    let (encoding, quoted) = Encoding::split(spelling);
+    let delimiter = match quoted.as_bytes() {
+        [open @ (b'"' | b'\''), .., close] if open == close => *open,
+        _ => return None,
+    };
+    let mut decoder = Decoder {
+        text: &quoted[1..quoted.len() - 1],
+        offset: spelling.len() - quoted.len() + 1,
+        pos: 0,
+        unit_width: encoding.unit_width(target),
+        units: vec![],
+        errors: vec![],
+    };
+    decoder.run();
+    Some(Literal {
+        encoding,
+        delimiter,
+        units: decoder.units,
+        errors: decoder.errors,
+    })
+}
+
+struct Decoder<'a> {
+    // between the quotes
+    text: &'a [u8],
+    // where `text` starts in the spelling
+    offset: usize,
+    pos: usize,
+    unit_width: u32,
+    units: Vec<u32>,
+    errors: Vec<(Range<usize>, EscapeError)>,
+}
+
+impl Decoder<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn run(&mut self) {
+        while let Some(byte) = self.peek() {
+            if byte == b'\\' {
+                self.pos += 1;
+                self.escape();
+                continue;
+            }
+            match bstr::decode_utf8(&self.text[self.pos..]) {
+                (Some(ch), len) => {
+                    self.push_char(ch);
+                    self.pos += len;
+                }
+                // not UTF-8; the byte is passed through
+                (None, _) => {
+                    self.push_unit(byte.into());
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    // the escape after a `\`.
+    fn escape(&mut self) {
+        let start = self.pos - 1;
+        let Some(ch) = self.peek() else {
+            return self.push_unit(b'\\'.into());
+        };
+        self.pos += 1;
+        let value = match ch {
+            b'\'' | b'"' | b'?' | b'\\' => ch.into(),
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'n' => 0x0a,
+            b'r' => 0x0d,
+            b't' => 0x09,
+            b'v' => 0x0b,
+            // a GNU extension
+            b'e' | b'E' => 0x1b,
+            b'0'..=b'7' => {
+                self.pos -= 1;
+                self.digits(8, 3, start)
+            }
+            b'o' if self.peek() == Some(b'{') => self.delimited(8, start),
+            b'x' if self.peek() == Some(b'{') => self.delimited(16, start),
+            b'x' => self.digits(16, usize::MAX, start),
+            b'u' | b'U' => return self.universal(ch, start),
+            _ => {
+                // the whole character, which may take several bytes
+                let (ch, len) = bstr::decode_utf8(&self.text[start + 1..]);
+                self.pos = start + 1 + len.max(1);
+                self.error(start, EscapeError::Unknown);
+                return match ch {
+                    Some(ch) => self.push_char(ch),
+                    None => self.push_unit(self.text[start + 1].into()),
+                };
+            }
+        };
+        if value > self.max_unit().into() {
+            self.error(start, EscapeError::OutOfRange);
+        }
+        self.push_unit(value as u32);
+    }
+
+    // `\uXXXX`, `\UXXXXXXXX` or `\u{...}`.
+    fn universal(&mut self, ch: u8, start: usize) {
+        let (value, complete) = match ch {
+            b'u' if self.peek() == Some(b'{') => (self.delimited(16, start), true),
+            _ => {
+                let len = if ch == b'u' { 4 } else { 8 };
+                let digits_start = self.pos;
+                let value = self.digits(16, len, start);
+                // having none is already reported
+                let count = self.pos - digits_start;
+                (value, count == 0 || count == len)
+            }
+        };
+        let ch = u32::try_from(value).ok().and_then(char::from_u32);
+        match ch {
+            _ if !complete => self.error(start, EscapeError::IncompleteUcn),
+            None => self.error(start, EscapeError::InvalidUcn),
+            Some(_) => {}
+        }
+        self.push_char(ch.unwrap_or(char::REPLACEMENT_CHARACTER));
+    }
+
+    // up to `max_len` digits in `radix`, for the escape at `start`. a value
+    // too big for any code unit saturates.
+    fn digits(&mut self, radix: u32, max_len: usize, start: usize) -> u64 {
+        let digits_start = self.pos;
+        let mut value = 0u64;
+        while self.pos - digits_start < max_len {
+            let Some(digit) = self.peek().and_then(|b| char::from(b).to_digit(radix)) else {
+                break;
+            };
+            value = value
+                .saturating_mul(radix.into())
+                .saturating_add(digit.into());
+            self.pos += 1;
+        }
+        if self.pos == digits_start {
+            self.error(start, EscapeError::MissingDigits);
+        }
+        value
+    }
+
+    // `{digits}`, as in C++23's `\x{...}`, `\o{...}` and `\u{...}`.
+    fn delimited(&mut self, radix: u32, start: usize) -> u64 {
+        self.pos += 1;
+        let value = self.digits(radix, usize::MAX, start);
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+        } else {
+            self.error(start, EscapeError::Unterminated);
+        }
+        value
+    }
+
+    // report the escape from `start` to here.
+    fn error(&mut self, start: usize, error: fn(BString) -> EscapeError) {
+        let escape = self.text[start..self.pos].into();
+        let span = self.offset + start..self.offset + self.pos;
+        self.errors.push((span, error(escape)));
+    }
+
+    fn max_unit(&self) -> u32 {
+        u32::MAX >> (32 - self.unit_width)
+    }
+
+    fn push_unit(&mut self, value: u32) {
+        self.units.push(value & self.max_unit());
+    }
+
+    fn push_char(&mut self, ch: char) {
+        match self.unit_width {
+            8 => {
+                let mut buf = [0; 4];
+                let bytes = ch.encode_utf8(&mut buf).bytes();
+                self.units.extend(bytes.map(u32::from));
+            }
+            16 => {
+                let mut buf = [0; 2];
+                let units = ch.encode_utf16(&mut buf).iter();
+                self.units.extend(units.map(|&unit| u32::from(unit)));
+            }
+            _ => self.units.push(ch.into()),
+        }
+    }
+}
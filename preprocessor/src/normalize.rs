@@ -0,0 +1,66 @@
+use crate::token::Punct;
+use crate::token::Token;
+
+// the preprocessor's output as phase 7 wants it: no `Eol`s, and exactly one
+// `Eof`, at the end, whether the stream had none or several. errors are
+// passed along where they occur.
+pub struct Normalized<Tokens> {
+    tokens: Tokens,
+    strip_passthrough: bool,
+    // the next token starts a line
+    line_start: bool,
+    // dropping the rest of a passed-through directive
+    in_directive: bool,
+    finished: bool,
+}
+
+impl<Tokens> Normalized<Tokens> {
+    pub fn new(tokens: Tokens) -> Self {
+        Self {
+            tokens,
+            strip_passthrough: false,
+            line_start: true,
+            in_directive: false,
+            finished: false,
+        }
+    }
+
+    // drop the directive lines the parser passes through, such as those
+    // `OutputOptions::keep_defines` asks for, which are not C tokens.
+    pub fn with_passthrough_stripped(mut self, strip: bool) -> Self {
+        self.strip_passthrough = strip;
+        self
+    }
+}
+
+impl<'a, E, Tokens> Iterator for Normalized<Tokens>
+where
+    Tokens: Iterator<Item = Result<Token<'a>, E>>,
+{
+    type Item = Result<Token<'a>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let tok = match self.tokens.next() {
+                Some(Ok(tok)) => tok,
+                Some(Err(e)) => return Some(Err(e)),
+                None => Token::Eof,
+            };
+            let line_start = std::mem::replace(&mut self.line_start, tok == Token::Eol);
+            match tok {
+                Token::Eof => {
+                    self.finished = true;
+                    return Some(Ok(tok));
+                }
+                Token::Eol => self.in_directive = false,
+                _ if self.in_directive => {}
+                // code lines never start with `#`
+                Token::Punct(Punct::Hash) if line_start && self.strip_passthrough => {
+                    self.in_directive = true;
+                }
+                tok => return Some(Ok(tok)),
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,390 @@
+use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice;
+
+use crate::target::Target;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    Decimal,
+    Octal,
+    Hex,
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberKind {
+    Integer,
+    Floating,
+}
+
+// a pp-number cut into the parts of a constant. each part keeps its `'`
+// separators, and whatever doesn't fit the earlier parts is the suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parts<'a> {
+    pub kind: NumberKind,
+    pub base: Base,
+    // `0x`, `0b` and their capitals; empty for decimal and octal
+    pub prefix: &'a BStr,
+    // the digits before any `.`
+    pub digits: &'a BStr,
+    // the digits after the `.`, if there is one
+    pub fraction: Option<&'a BStr>,
+    // the sign and digits after `e` or `p`, if there is one
+    pub exponent: Option<&'a BStr>,
+    pub suffix: &'a BStr,
+}
+
+// the types an integer constant can have. `int` is 32 bits and `long long`
+// 64 on every target; `long` is either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerType {
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+    // C23's `_BitInt(N)`, from a `wb` suffix
+    BitInt(u32),
+    UnsignedBitInt(u32),
+}
+
+// the value and type of an integer constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Integer {
+    pub value: u128,
+    pub ty: IntegerType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IntegerError {
+    #[error("not an integer constant")]
+    NotInteger,
+    #[error("invalid digit `{0}` in {1} constant")]
+    InvalidDigit(char, &'static str),
+    #[error("invalid suffix `{0}` on integer constant")]
+    InvalidSuffix(BString),
+    #[error("integer constant is too large for its type")]
+    TooLarge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatType {
+    Float,
+    Double,
+    LongDouble,
+    // the decimal types from `df`, `dd` and `dl`
+    Decimal32,
+    Decimal64,
+    Decimal128,
+}
+
+// the value and type of a floating constant. `long double` and the decimal
+// types are held as the nearest `f64`, so values past its range come out
+// infinite rather than being reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Float {
+    pub value: f64,
+    pub ty: FloatType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FloatError {
+    #[error("not a floating constant")]
+    NotFloat,
+    #[error("no digits in floating constant")]
+    NoDigits,
+    #[error("exponent has no digits")]
+    EmptyExponent,
+    #[error("hexadecimal floating constant requires an exponent")]
+    MissingBinaryExponent,
+    #[error("invalid suffix `{0}` on floating constant")]
+    InvalidSuffix(BString),
+    #[error("floating constant exceeds the range of `{0}`")]
+    OutOfRange(&'static str),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Size {
+    Int,
+    Long,
+    LongLong,
+    BitInt,
+}
+
+impl IntegerType {
+    pub fn is_unsigned(self) -> bool {
+        matches!(
+            self,
+            Self::UnsignedInt
+                | Self::UnsignedLong
+                | Self::UnsignedLongLong
+                | Self::UnsignedBitInt(_)
+        )
+    }
+
+    // the largest value the type holds on `target`.
+    pub fn max(self, target: &Target) -> u128 {
+        let long = target.long_width();
+        match self {
+            Self::Int => i32::MAX as u128,
+            Self::UnsignedInt => u32::MAX.into(),
+            Self::Long => u128::MAX >> (129 - long),
+            Self::UnsignedLong => u128::MAX >> (128 - long),
+            Self::LongLong => i64::MAX as u128,
+            Self::UnsignedLongLong => u64::MAX.into(),
+            Self::BitInt(width) => u128::MAX >> (129 - width),
+            Self::UnsignedBitInt(width) => u128::MAX >> (128 - width),
+        }
+    }
+}
+
+// split the spelling of a `Token::Number`. a constant with a `.` or an
+// exponent is floating; anything else is an integer, octal if it starts
+// with `0`.
+pub fn split(spelling: &BStr) -> Parts<'_> {
+    let (base, prefix_len) = match (spelling.get(..2), spelling.get(2)) {
+        (Some(b"0x" | b"0X"), Some(b)) if b.is_ascii_hexdigit() || *b == b'.' => (Base::Hex, 2),
+        (Some(b"0b" | b"0B"), Some(b)) if b.is_ascii_digit() => (Base::Binary, 2),
+        _ => (Base::Decimal, 0),
+    };
+    let (prefix, rest) = spelling.split_at(prefix_len);
+    let radix = if base == Base::Hex { 16 } else { 10 };
+    let run = |text: &'_ [u8]| {
+        let is_digit = |&b: &u8| char::from(b).is_digit(radix) || b == b'\'';
+        text.iter().position(|b| !is_digit(b)).unwrap_or(text.len())
+    };
+
+    let (digits, mut rest) = rest.split_at(run(rest));
+    let mut fraction = None;
+    if let (Some(b'.'), false) = (rest.first(), base == Base::Binary) {
+        let (part, after) = rest[1..].split_at(run(&rest[1..]));
+        fraction = Some(part.as_bstr());
+        rest = after;
+    }
+    let mut exponent = None;
+    if let (Some(b'e' | b'E'), false) | (Some(b'p' | b'P'), true) =
+        (rest.first(), base == Base::Hex)
+    {
+        let sign = usize::from(matches!(rest.get(1), Some(b'+' | b'-')));
+        let len = rest[1 + sign..]
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .unwrap_or(rest.len() - 1 - sign);
+        let (part, after) = rest[1..].split_at(sign + len);
+        exponent = Some(part.as_bstr());
+        rest = after;
+    }
+
+    let kind = if fraction.is_some() || exponent.is_some() {
+        NumberKind::Floating
+    } else {
+        NumberKind::Integer
+    };
+    let base = match base {
+        Base::Decimal if kind == NumberKind::Integer && digits.starts_with(b"0") => Base::Octal,
+        base => base,
+    };
+    Parts {
+        kind,
+        base,
+        prefix: prefix.as_bstr(),
+        digits: digits.as_bstr(),
+        fraction,
+        exponent,
+        suffix: rest.as_bstr(),
+    }
+}
+
+// decode the spelling of a `Token::Number` as an integer constant: its base
+// prefix, digits with optional `'` separators, and a suffix of `u` with one
+// of `l`, `ll` or `wb`, in either order. the type is the first of the
+// standard's list for that base and suffix that can hold the value on
+// `target`.
+pub fn decode_integer(spelling: &BStr, target: &Target) -> Result<Integer, IntegerError> {
+    let parts = split(spelling);
+    if parts.kind == NumberKind::Floating {
+        return Err(IntegerError::NotInteger);
+    }
+    let radix: u32 = match parts.base {
+        Base::Decimal => 10,
+        Base::Octal => 8,
+        Base::Hex => 16,
+        Base::Binary => 2,
+    };
+    let digits = parts.digits;
+    let suffix = parts.suffix;
+
+    let mut value = 0u128;
+    for &b in digits.iter().filter(|&&b| b != b'\'') {
+        let digit = char::from(b).to_digit(16).unwrap();
+        if digit >= radix {
+            let base = if radix == 2 { "binary" } else { "octal" };
+            return Err(IntegerError::InvalidDigit(b.into(), base));
+        }
+        value = value
+            .checked_mul(radix.into())
+            .and_then(|value| value.checked_add(digit.into()))
+            .ok_or(IntegerError::TooLarge)?;
+    }
+
+    let invalid_suffix = || IntegerError::InvalidSuffix(suffix.into());
+    let (unsigned, size) = parse_suffix(suffix).ok_or_else(invalid_suffix)?;
+    let ty = match size {
+        Size::BitInt => {
+            let bits = 128 - value.leading_zeros();
+            if unsigned {
+                IntegerType::UnsignedBitInt(bits.max(1))
+            } else if bits < 128 {
+                IntegerType::BitInt((bits + 1).max(2))
+            } else {
+                return Err(IntegerError::TooLarge);
+            }
+        }
+        _ => candidates(size, unsigned, radix == 10)
+            .iter()
+            .copied()
+            .find(|ty| value <= ty.max(target))
+            .ok_or(IntegerError::TooLarge)?,
+    };
+    Ok(Integer { value, ty })
+}
+
+// `u` or `U`, and a size, in either order. `ll` can't mix cases.
+fn parse_suffix(suffix: &[u8]) -> Option<(bool, Size)> {
+    let (unsigned, rest) = strip_unsigned(suffix);
+    let (size, rest) = match rest {
+        [b'l', b'l', rest @ ..] | [b'L', b'L', rest @ ..] => (Size::LongLong, rest),
+        [b'l' | b'L', rest @ ..] => (Size::Long, rest),
+        [b'w', b'b', rest @ ..] | [b'W', b'B', rest @ ..] => (Size::BitInt, rest),
+        _ => (Size::Int, rest),
+    };
+    let (unsigned, rest) = match unsigned {
+        true => (true, rest),
+        false => strip_unsigned(rest),
+    };
+    rest.is_empty().then_some((unsigned, size))
+}
+
+fn strip_unsigned(suffix: &[u8]) -> (bool, &[u8]) {
+    match suffix {
+        [b'u' | b'U', rest @ ..] => (true, rest),
+        _ => (false, suffix),
+    }
+}
+
+// C17 6.4.4.1p5. decimal constants without `u` stay signed.
+fn candidates(size: Size, unsigned: bool, decimal: bool) -> &'static [IntegerType] {
+    use IntegerType::*;
+    match (size, unsigned, decimal) {
+        (Size::Int, false, true) => &[Int, Long, LongLong],
+        (Size::Int, false, false) => &[
+            Int,
+            UnsignedInt,
+            Long,
+            UnsignedLong,
+            LongLong,
+            UnsignedLongLong,
+        ],
+        (Size::Int, true, _) => &[UnsignedInt, UnsignedLong, UnsignedLongLong],
+        (Size::Long, false, true) => &[Long, LongLong],
+        (Size::Long, false, false) => &[Long, UnsignedLong, LongLong, UnsignedLongLong],
+        (Size::Long, true, _) => &[UnsignedLong, UnsignedLongLong],
+        (Size::LongLong, false, true) => &[LongLong],
+        (Size::LongLong, false, false) => &[LongLong, UnsignedLongLong],
+        (Size::LongLong, true, _) => &[UnsignedLongLong],
+        (Size::BitInt, ..) => &[],
+    }
+}
+
+// decode the spelling of a `Token::Number` as a floating constant: decimal
+// or hexadecimal digits with a `.` or an exponent, and a suffix of `f`, `l`,
+// `df`, `dd` or `dl`, the last three only on decimal constants.
+pub fn decode_float(spelling: &BStr) -> Result<Float, FloatError> {
+    let parts = split(spelling);
+    if parts.kind == NumberKind::Integer {
+        return Err(FloatError::NotFloat);
+    }
+    let hex = parts.base == Base::Hex;
+    let without_separators =
+        |part: &BStr| -> String { part.chars().filter(|&ch| ch != '\'').collect() };
+    let int = without_separators(parts.digits);
+    let frac = without_separators(parts.fraction.unwrap_or_default());
+    if int.is_empty() && frac.is_empty() {
+        return Err(FloatError::NoDigits);
+    }
+    let exponent = match parts.exponent {
+        Some(exponent) if !exponent.iter().any(u8::is_ascii_digit) => {
+            return Err(FloatError::EmptyExponent);
+        }
+        Some(exponent) => {
+            let value = exponent
+                .iter()
+                .filter(|b| b.is_ascii_digit())
+                .fold(0i64, |value, &b| {
+                    value.saturating_mul(10).saturating_add((b - b'0').into())
+                });
+            if exponent.starts_with(b"-") {
+                -value
+            } else {
+                value
+            }
+        }
+        None if hex => return Err(FloatError::MissingBinaryExponent),
+        None => 0,
+    };
+
+    let ty = match parts.suffix.as_bytes() {
+        b"" => FloatType::Double,
+        b"f" | b"F" => FloatType::Float,
+        b"l" | b"L" => FloatType::LongDouble,
+        b"df" | b"DF" if !hex => FloatType::Decimal32,
+        b"dd" | b"DD" if !hex => FloatType::Decimal64,
+        b"dl" | b"DL" if !hex => FloatType::Decimal128,
+        suffix => return Err(FloatError::InvalidSuffix(suffix.into())),
+    };
+    let value = if hex {
+        let value = hex_value(int.as_bytes(), frac.as_bytes(), exponent);
+        match ty {
+            FloatType::Float => value as f32 as f64,
+            _ => value,
+        }
+    } else {
+        // digits, a `.` and an exponent, which Rust's float syntax accepts
+        let decimal = format!("{int}.{frac}e{exponent}");
+        match ty {
+            FloatType::Float => decimal.parse::<f32>().unwrap().into(),
+            _ => decimal.parse::<f64>().unwrap(),
+        }
+    };
+    match ty {
+        FloatType::Float if value.is_infinite() => Err(FloatError::OutOfRange("float")),
+        FloatType::Double if value.is_infinite() => Err(FloatError::OutOfRange("double")),
+        _ => Ok(Float { value, ty }),
+    }
+}
+
+// the digits before and after the `.`, times 2 to the `exponent`.
+fn hex_value(int: &[u8], frac: &[u8], exponent: i64) -> f64 {
+    let mut mantissa = 0u64;
+    let mut exponent = exponent.saturating_sub(4 * frac.len() as i64);
+    for &b in int.iter().chain(frac) {
+        let digit = char::from(b).to_digit(16).unwrap();
+        if mantissa >> 60 == 0 {
+            mantissa = mantissa << 4 | u64::from(digit);
+        } else {
+            // past what a double can hold; only the magnitude matters
+            exponent = exponent.saturating_add(4);
+        }
+    }
+    // anything further out is zero or infinite whatever the mantissa
+    let mut exponent = exponent.clamp(-5000, 5000) as i32;
+    let mut value = mantissa as f64;
+    while exponent.abs() > 1000 {
+        let step = 1000 * exponent.signum();
+        value *= 2f64.powi(step);
+        exponent -= step;
+    }
+    value * 2f64.powi(exponent)
+}
@@ -0,0 +1,94 @@
+use crate::target::Target;
+
+#[derive(Debug, Clone)]
+pub struct LangOptions {
+    // `#assert`, `#include_next`, `$` in identifiers and the like
+    pub gnu_extensions: bool,
+    // ignored when `cplusplus` is set
+    pub c_standard: CStandard,
+    // preprocess C++ instead of C
+    pub cplusplus: Option<CxxStandard>,
+    // `<:`, `:>`, `<%`, `%>`, `%:` and `%:%:`
+    pub digraphs: bool,
+    // decides the predefined macros and the sizes of types
+    pub target: Target,
+}
+
+impl Default for LangOptions {
+    fn default() -> Self {
+        Self {
+            gnu_extensions: false,
+            c_standard: CStandard::C23,
+            cplusplus: None,
+            digraphs: true,
+            target: Target::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CStandard {
+    C89,
+    C99,
+    C11,
+    C17,
+    C23,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CxxStandard {
+    Cxx98,
+    Cxx11,
+    Cxx14,
+    Cxx17,
+    Cxx20,
+    Cxx23,
+}
+
+impl CStandard {
+    // the value of `__STDC_VERSION__`; C89 has none.
+    pub fn version(self) -> Option<&'static str> {
+        match self {
+            Self::C89 => None,
+            Self::C99 => Some("199901L"),
+            Self::C11 => Some("201112L"),
+            Self::C17 => Some("201710L"),
+            Self::C23 => Some("202311L"),
+        }
+    }
+}
+
+impl CxxStandard {
+    // the value of `__cplusplus`.
+    pub fn version(self) -> &'static str {
+        match self {
+            Self::Cxx98 => "199711L",
+            Self::Cxx11 => "201103L",
+            Self::Cxx14 => "201402L",
+            Self::Cxx17 => "201703L",
+            Self::Cxx20 => "202002L",
+            Self::Cxx23 => "202302L",
+        }
+    }
+
+    // the standard whose `__cplusplus` is `version`.
+    pub fn from_version(version: i64) -> Option<Self> {
+        match version {
+            199711 => Some(Self::Cxx98),
+            201103 => Some(Self::Cxx11),
+            201402 => Some(Self::Cxx14),
+            201703 => Some(Self::Cxx17),
+            202002 => Some(Self::Cxx20),
+            202302 => Some(Self::Cxx23),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OutputOptions {
+    // pass `#pragma region` and `#pragma endregion` through to the output
+    pub keep_region_pragmas: bool,
+    // pass `#define` and `#undef` through after recording them
+    pub keep_defines: bool,
+}
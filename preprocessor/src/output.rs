@@ -0,0 +1,70 @@
+use bstr::BString;
+
+use crate::lexer::lex_with_options;
+use crate::options::LangOptions;
+use crate::token::Punct;
+use crate::token::Token;
+
+// turns a token stream back into source text that lexes to the same tokens.
+pub struct TextWriter<'a> {
+    options: LangOptions,
+    out: Vec<u8>,
+    // the last two tokens on the line, with where each starts in `out`.
+    // some pastes only show up across three tokens, e.g. `..` `.`.
+    recent: Vec<(Token<'a>, usize)>,
+}
+
+impl<'a> TextWriter<'a> {
+    pub fn new(options: LangOptions) -> Self {
+        Self {
+            options,
+            out: vec![],
+            recent: vec![],
+        }
+    }
+
+    // `space_before` keeps whitespace the input had; a space is also added
+    // wherever the tokens would otherwise run together.
+    pub fn write(&mut self, tok: Token<'a>, space_before: bool) {
+        match tok {
+            Token::Eol => {
+                self.out.push(b'\n');
+                self.recent.clear();
+            }
+            Token::Eof => {}
+            tok => {
+                if !self.recent.is_empty() && (space_before || self.would_paste(tok)) {
+                    self.out.push(b' ');
+                }
+                if self.recent.len() == 2 {
+                    self.recent.remove(0);
+                }
+                self.recent.push((tok, self.out.len()));
+                self.out.extend_from_slice(tok.spelling());
+            }
+        }
+    }
+
+    pub fn finish(self) -> BString {
+        self.out.into()
+    }
+
+    fn would_paste(&self, next: Token<'_>) -> bool {
+        let Some(&(prev, _)) = self.recent.last() else {
+            return false;
+        };
+        // comments are gone by the time we lex, so `/` `/` would survive a
+        // round trip here but not through a compiler.
+        if prev == Token::Punct(Punct::Slash)
+            && matches!(next.spelling().first(), Some(b'/' | b'*'))
+        {
+            return true;
+        }
+        let mut text = BString::from(&self.out[self.recent[0].1..]);
+        text.extend_from_slice(next.spelling());
+        let relexed = lex_with_options(text.as_ref(), self.options.clone())
+            .take_while(|tok| *tok != Token::Eof);
+        let expected = self.recent.iter().map(|&(tok, _)| tok).chain([next]);
+        !relexed.eq(expected)
+    }
+}
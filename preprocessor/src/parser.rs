@@ -1,18 +1,57 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::hash::BuildHasherDefault;
+use std::hash::Hasher;
 
+use bstr::BStr;
 use bstr::BString;
 use bstr::ByteSlice;
 use itertools::Itertools;
 use itertools::MultiPeek;
 use rand_core::RngCore;
+use unicode_normalization::is_nfc_quick;
+use unicode_normalization::IsNormalized;
+use unicode_normalization::UnicodeNormalization;
 use wyhash::WyHash;
 use wyhash::WyRng;
 
+use crate::callbacks::Callbacks;
+use crate::callbacks::DirectiveHandler;
+use crate::callbacks::NoCallbacks;
+use crate::coverage::CoverageReport;
+use crate::coverage::Group;
+use crate::coverage::GroupState;
+use crate::definition::BodyToken;
+use crate::definition::Macro;
+use crate::diagnostic::Diagnostic;
+use crate::expr::Evaluator;
+use crate::files::FileProvider;
+use crate::files::NoFiles;
+use crate::include_graph::IncludeGraph;
+use crate::index::MacroIndex;
+use crate::index::Site;
+use crate::lexer::lex_with_options;
+use crate::lexer::Lexed;
+use crate::limits::TranslationLimits;
+use crate::literal;
+use crate::literal::CharError;
+use crate::number::IntegerError;
+use crate::options::CStandard;
+use crate::options::CxxStandard;
+use crate::options::LangOptions;
+use crate::options::OutputOptions;
+use crate::pragma::DiagnosticPragma;
+use crate::pragma::Pragma;
+use crate::pragma::StdcState;
+use crate::stats::Stats;
 use crate::token::Punct;
 use crate::token::Token;
+use crate::warning::Warning;
+use crate::warning::WarningFlag;
+use crate::warning::WarningOptions;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum Directive {
     If,
     Ifdef,
@@ -21,14 +60,107 @@ enum Directive {
     Else,
     Endif,
     Include,
+    IncludeNext,
     Define,
     Undef,
     Line,
     Error,
     Pragma,
+    Assert,
+    Unassert,
+    Ident,
 }
 
-#[derive(Debug, thiserror::Error)]
+impl Directive {
+    const ALL: [Self; 16] = [
+        Self::If,
+        Self::Ifdef,
+        Self::Ifndef,
+        Self::Elif,
+        Self::Else,
+        Self::Endif,
+        Self::Include,
+        Self::IncludeNext,
+        Self::Define,
+        Self::Undef,
+        Self::Line,
+        Self::Error,
+        Self::Pragma,
+        Self::Assert,
+        Self::Unassert,
+        Self::Ident,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::If => "if",
+            Self::Ifdef => "ifdef",
+            Self::Ifndef => "ifndef",
+            Self::Elif => "elif",
+            Self::Else => "else",
+            Self::Endif => "endif",
+            Self::Include => "include",
+            Self::IncludeNext => "include_next",
+            Self::Define => "define",
+            Self::Undef => "undef",
+            Self::Line => "line",
+            Self::Error => "error",
+            Self::Pragma => "pragma",
+            Self::Assert => "assert",
+            Self::Unassert => "unassert",
+            Self::Ident => "ident",
+        }
+    }
+
+    fn is_gnu_extension(self) -> bool {
+        matches!(self, Self::IncludeNext | Self::Assert | Self::Unassert)
+    }
+
+    fn is_conditional(self) -> bool {
+        matches!(
+            self,
+            Self::If | Self::Ifdef | Self::Ifndef | Self::Elif | Self::Else | Self::Endif
+        )
+    }
+}
+
+// a function-like macro invocation whose arguments haven't been closed yet.
+struct Invocation {
+    name: BString,
+    // where its `(` is
+    line: u32,
+    // of parentheses, the invocation's own included
+    depth: usize,
+    // at the top level, so far
+    commas: usize,
+    // nothing has come since the invocation's `(`
+    empty: bool,
+}
+
+// what a directive name means.
+#[derive(Clone, Copy)]
+enum Handler {
+    Builtin(Directive),
+    // an index into `Parser::extensions`
+    Extension(usize),
+}
+
+// one level of conditional nesting.
+struct Conditional {
+    // the directive that opened the current group
+    directive: Directive,
+    // whether the enclosing group is being processed
+    parent_active: bool,
+    // some group has been processed already
+    taken: bool,
+    // the current group is being processed
+    active: bool,
+    // whether a group was reached depends on a condition that couldn't be
+    // evaluated, here or in an enclosing conditional
+    unknown: bool,
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
 pub enum ParseError {
     #[error("missing directive name")]
     MissingDirectiveName,
@@ -36,6 +168,137 @@ pub enum ParseError {
     InvalidDirective(BString),
     #[error("`elif` has no `if` to bind to")]
     MismatchedElif,
+    #[error("`else` has no `if` to bind to")]
+    MismatchedElse,
+    #[error("`else` after `else`")]
+    ElseAfterElse,
+    #[error("`endif` has no `if` to bind to")]
+    MismatchedEndif,
+    #[error("#if with no expression")]
+    MissingCondition,
+    #[error("missing operand in #if expression")]
+    MissingOperandInCondition,
+    #[error("unexpected `{0}` in #if expression")]
+    UnexpectedInCondition(BString),
+    #[error("expected `{0}` in #if expression")]
+    ExpectedInCondition(&'static str),
+    #[error("invalid integer `{0}` in #if expression: {1}")]
+    InvalidIntegerInCondition(BString, IntegerError),
+    #[error("division by zero in #if expression")]
+    DivisionByZeroInCondition,
+    #[error("operator `defined` requires an identifier")]
+    MissingDefinedOperand,
+    #[error("macro name missing")]
+    MissingMacroName,
+    #[error("macro names must be identifiers, found `{0}`")]
+    InvalidMacroName(BString),
+    #[error("assertion without predicate")]
+    MissingPredicate,
+    #[error("missing answer in assertion")]
+    MissingAnswer,
+    #[error("missing `)` to complete answer")]
+    UnterminatedAnswer,
+    #[error("extra tokens after assertion")]
+    ExtraTokensAfterAssertion,
+    #[error("invalid #ident directive")]
+    InvalidIdent,
+    #[error("#line directive requires a line number")]
+    MissingLineNumber,
+    #[error("extra tokens at end of #line directive")]
+    ExtraTokensAfterLine,
+    #[error("expected ON, OFF or DEFAULT, found `{0}`")]
+    InvalidOnOffSwitch(BString),
+    #[error("#pragma message requires a string")]
+    EmptyPragmaMessage,
+    #[error("missing `)` after #pragma message")]
+    UnterminatedPragmaMessage,
+    #[error("{0} is not a valid line number")]
+    InvalidLineNumber(BString),
+    #[error("#include expects \"FILENAME\" or <FILENAME>")]
+    InvalidInclude,
+    #[error("invalid filename {0}")]
+    InvalidFileName(BString),
+    #[error("invalid flag {0} in line directive")]
+    InvalidLinemarkerFlag(BString),
+    #[error("expected push, pop, ignored, warning or error with a \"-W\" option, found `{0}`")]
+    InvalidDiagnosticPragma(BString),
+    #[error("pasting `{0}` and `{1}` gives `{2}`, which is not a valid preprocessing token")]
+    InvalidPaste(BString, BString, BString),
+    #[error("unterminated `#{0}`")]
+    UnterminatedConditional(&'static str),
+    #[error("#error {0}")]
+    ErrorDirective(BString),
+    #[error("{1}: `{0}`")]
+    InvalidCharConstant(BString, CharError),
+    #[error("expected a parameter name, found `{0}`")]
+    ExpectedMacroParameter(BString),
+    #[error("expected `,` or `)` in macro parameter list, found `{0}`")]
+    ExpectedCommaInMacroParameters(BString),
+    #[error("missing `)` in macro parameter list")]
+    UnterminatedMacroParameters,
+    #[error("duplicate macro parameter `{0}`")]
+    DuplicateMacroParameter(BString),
+    #[error("`#` is not followed by a macro parameter")]
+    StringifyWithoutParameter,
+    #[error("`##` cannot appear at either end of a macro expansion")]
+    PasteAtEdge,
+    #[error("#{0}: {1}")]
+    ExtensionDirective(BString, String),
+    #[error("unterminated argument list invoking macro `{0}`, opened on line {1}")]
+    UnterminatedInvocation(BString, u32),
+    #[error("expected a rounding direction, found `{0}`")]
+    InvalidRoundingDirection(BString),
+}
+
+impl ParseError {
+    // stable across releases; a retired code is never reused.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingDirectiveName => "PP0001",
+            Self::InvalidDirective(_) => "PP0002",
+            Self::MismatchedElif => "PP0003",
+            Self::MismatchedElse => "PP0004",
+            Self::ElseAfterElse => "PP0005",
+            Self::MismatchedEndif => "PP0006",
+            Self::MissingCondition => "PP0007",
+            Self::MissingOperandInCondition => "PP0008",
+            Self::UnexpectedInCondition(_) => "PP0009",
+            Self::ExpectedInCondition(_) => "PP0010",
+            Self::InvalidIntegerInCondition(..) => "PP0011",
+            Self::DivisionByZeroInCondition => "PP0012",
+            Self::MissingDefinedOperand => "PP0013",
+            Self::MissingMacroName => "PP0014",
+            Self::InvalidMacroName(_) => "PP0015",
+            Self::MissingPredicate => "PP0016",
+            Self::MissingAnswer => "PP0017",
+            Self::UnterminatedAnswer => "PP0018",
+            Self::ExtraTokensAfterAssertion => "PP0019",
+            Self::InvalidIdent => "PP0020",
+            Self::MissingLineNumber => "PP0021",
+            Self::ExtraTokensAfterLine => "PP0022",
+            Self::InvalidOnOffSwitch(_) => "PP0024",
+            Self::EmptyPragmaMessage => "PP0025",
+            Self::UnterminatedPragmaMessage => "PP0026",
+            Self::InvalidLineNumber(_) => "PP0027",
+            Self::InvalidInclude => "PP0028",
+            Self::InvalidFileName(_) => "PP0029",
+            Self::InvalidLinemarkerFlag(_) => "PP0030",
+            Self::InvalidDiagnosticPragma(_) => "PP0031",
+            Self::InvalidPaste(..) => "PP0032",
+            Self::UnterminatedConditional(_) => "PP0033",
+            Self::ErrorDirective(_) => "PP0034",
+            Self::InvalidCharConstant(..) => "PP0035",
+            Self::ExpectedMacroParameter(_) => "PP0036",
+            Self::ExpectedCommaInMacroParameters(_) => "PP0037",
+            Self::UnterminatedMacroParameters => "PP0038",
+            Self::DuplicateMacroParameter(_) => "PP0039",
+            Self::StringifyWithoutParameter => "PP0040",
+            Self::PasteAtEdge => "PP0041",
+            Self::ExtensionDirective(..) => "PP0042",
+            Self::UnterminatedInvocation(..) => "PP0043",
+            Self::InvalidRoundingDirection(_) => "PP0044",
+        }
+    }
 }
 
 struct Hash(WyHash);
@@ -45,128 +308,1370 @@ impl Default for Hash {
     }
 }
 
-type MacroTable = HashMap<BString, BString, BuildHasherDefault<Hash>>;
+impl Hasher for Hash {
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes)
+    }
+}
+
+type MacroTable<'a> = HashMap<BString, Macro<'a>, BuildHasherDefault<Hash>>;
+type DirectiveTable = HashMap<BString, Handler, BuildHasherDefault<Hash>>;
+// predicate -> answers
+type AssertionTable = HashMap<BString, Vec<BString>, BuildHasherDefault<Hash>>;
 
-struct Parser<'a, Tokens>
+// the location reported for the current line, as adjusted by linemarkers.
+struct PresumedLocation {
+    line: u32,
+    file: Option<BString>,
+    system_header: bool,
+}
+
+pub struct Parser<'a, Tokens>
 where
-    Tokens: Iterator<Item = Token<'a>>,
+    Tokens: Iterator,
+    Tokens::Item: Lexed<'a>,
 {
-    macros: MacroTable,
+    options: LangOptions,
+    output_options: OutputOptions,
+    warning_options: WarningOptions,
+    callbacks: Box<dyn Callbacks>,
+    files: Box<dyn FileProvider>,
+    macros: MacroTable<'a>,
+    directives: DirectiveTable,
+    extensions: Vec<Box<dyn DirectiveHandler>>,
+    assertions: AssertionTable,
     tokens: MultiPeek<Tokens>,
-    // one token may yield many.
-    out_stack: VecDeque<Token<'a>>,
-    directives: Vec<Directive>,
+    // what `tokens` were lexed from, if known, and where in it the current
+    // line starts
+    text: Option<&'a BStr>,
+    line_start: usize,
+    // one token may yield many; each keeps whether whitespace preceded it.
+    out_stack: VecDeque<(Token<'a>, bool)>,
+    // for the token read last by `advance`, and the one handed out last
+    space_before_read: bool,
+    space_before: bool,
+    conditionals: Vec<Conditional>,
+    invocation: Option<Invocation>,
+    location: PresumedLocation,
+    stdc: StdcState,
+    limits: TranslationLimits,
+    // warnings and the errors they were promoted to
+    warnings: Vec<Diagnostic>,
+    // saved by `#pragma GCC diagnostic push`
+    warning_stack: Vec<WarningOptions>,
+    stats: Stats,
+    macro_index: MacroIndex,
+    include_graph: IncludeGraph,
+    coverage: CoverageReport,
+    // the `Eof` has been handed out
+    finished: bool,
 }
 
 impl<'a, Tokens> Parser<'a, Tokens>
 where
-    Tokens: Iterator<Item = Token<'a>>,
+    Tokens: Iterator,
+    Tokens::Item: Lexed<'a>,
 {
-    fn new(tokens: Tokens) -> Self {
+    pub fn new(tokens: Tokens, options: LangOptions) -> Self {
+        let mut macros = MacroTable::default();
+        let mut predefined = options.target.predefined_macros();
+        predefined.push(("__STDC__", "1"));
+        match options.cplusplus {
+            Some(std) => predefined.push(("__cplusplus", std.version())),
+            None => predefined.extend(
+                options
+                    .c_standard
+                    .version()
+                    .map(|version| ("__STDC_VERSION__", version)),
+            ),
+        }
+        for (name, value) in predefined {
+            let value = lex_with_options(BStr::new(value), options.clone());
+            let value = value.filter(|tok| !matches!(tok, Token::Eol | Token::Eof));
+            macros.insert(name.into(), Macro::object_like(value));
+        }
+        let mut directives = DirectiveTable::default();
+        for directive in Directive::ALL {
+            if options.gnu_extensions || !directive.is_gnu_extension() {
+                directives.insert(directive.name().into(), Handler::Builtin(directive));
+            }
+        }
+        directives.insert("sccs".into(), Handler::Builtin(Directive::Ident));
+        let limits = TranslationLimits::for_options(&options);
         Self {
-            macros: MacroTable::default(),
+            options,
+            output_options: OutputOptions::default(),
+            warning_options: WarningOptions::default(),
+            callbacks: Box::new(NoCallbacks),
+            files: Box::new(NoFiles),
+            macros,
+            directives,
+            extensions: vec![],
+            assertions: AssertionTable::default(),
             tokens: tokens.multipeek(),
+            text: None,
+            line_start: 0,
             out_stack: VecDeque::new(),
-            directives: vec![],
+            space_before_read: false,
+            space_before: false,
+            conditionals: vec![],
+            invocation: None,
+            location: PresumedLocation {
+                line: 1,
+                file: None,
+                system_header: false,
+            },
+            stdc: StdcState::default(),
+            limits,
+            warnings: vec![],
+            warning_stack: vec![],
+            stats: Stats::default(),
+            macro_index: MacroIndex::default(),
+            include_graph: IncludeGraph::default(),
+            coverage: CoverageReport::default(),
+            finished: false,
+        }
+    }
+
+    fn advance(&mut self) -> Option<Token<'a>> {
+        let lexed = self.tokens.next();
+        self.space_before_read = lexed.is_some_and(Lexed::space_before);
+        let tok = lexed.map(Lexed::token);
+        match tok {
+            Some(Token::Eol) => {
+                self.check_line_length();
+                self.location.line = self.location.line.wrapping_add(1);
+                self.stats.lines += 1;
+            }
+            Some(Token::Ident(id)) if id.contains(&b'$') => {
+                self.extension(format!("`$` in identifier `{id}` is an extension"));
+            }
+            Some(Token::Ident(id)) if !id.is_ascii() => self.check_normalization(id),
+            _ => {}
+        }
+        tok
+    }
+
+    // the line just ended against the limit on logical line length, which
+    // can only be checked if the text is known.
+    fn check_line_length(&mut self) {
+        let Some(text) = self.text else {
+            return;
+        };
+        let line = text.get(self.line_start..).unwrap_or_default();
+        let end = line.find_byte(b'\n').unwrap_or(line.len());
+        self.line_start += end + 1;
+        let length = line[..end].chars().count();
+        if !self.skipping() && length > self.limits.logical_line_length {
+            self.limit_exceeded(
+                "logical line length",
+                length,
+                self.limits.logical_line_length,
+            );
+        }
+    }
+
+    fn limit_exceeded(&mut self, what: &'static str, count: usize, max: usize) {
+        self.warn(Warning::LimitExceeded {
+            what,
+            count,
+            max,
+            line: self.location.line,
+        });
+    }
+
+    // C23 requires identifiers to be in Normalization Form C.
+    fn check_normalization(&mut self, ident: &BStr) {
+        let text = match ident.to_str() {
+            Ok(text) if is_nfc_quick(text.chars()) != IsNormalized::Yes => text,
+            _ => return,
+        };
+        let normalized: String = text.nfc().collect();
+        if normalized != text {
+            self.warn(Warning::NotNfc {
+                ident: ident.to_owned(),
+                normalized: normalized.into(),
+                line: self.location.line,
+            });
+        }
+    }
+
+    pub fn with_callbacks(mut self, callbacks: Box<dyn Callbacks>) -> Self {
+        self.callbacks = callbacks;
+        self
+    }
+
+    // where included headers are looked for; by default none are found.
+    pub fn with_file_provider(mut self, files: Box<dyn FileProvider>) -> Self {
+        self.files = files;
+        self
+    }
+
+    // the name the main file is reported under.
+    pub fn with_file_name(mut self, name: impl Into<BString>) -> Self {
+        self.location.file = Some(name.into());
+        self
+    }
+
+    pub fn with_output_options(mut self, output_options: OutputOptions) -> Self {
+        self.output_options = output_options;
+        self
+    }
+
+    pub fn with_warning_options(mut self, warning_options: WarningOptions) -> Self {
+        self.warning_options = warning_options;
+        self
+    }
+
+    // handle `#name` with `handler`. a name the preprocessor already
+    // understands keeps its built-in meaning.
+    pub fn with_directive(
+        mut self,
+        name: impl Into<BString>,
+        handler: impl DirectiveHandler + 'static,
+    ) -> Self {
+        let index = self.extensions.len();
+        if let Entry::Vacant(entry) = self.directives.entry(name.into()) {
+            entry.insert(Handler::Extension(index));
+            self.extensions.push(Box::new(handler));
+        }
+        self
+    }
+
+    // the text the tokens were lexed from. without it, `#define F (x)` can't
+    // be told from `#define F(x)`, and is taken to be function-like too.
+    pub fn with_text(mut self, text: &'a BStr) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    // define `name` before the input is read, as `-D` does.
+    // warn against `limits` rather than those of the language standard.
+    pub fn with_limits(mut self, limits: TranslationLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_define(mut self, name: impl Into<BString>, body: &'a BStr) -> Self {
+        let body = lex_with_options(body, self.options.clone())
+            .filter(|tok| !matches!(tok, Token::Eol | Token::Eof));
+        self.macros.insert(name.into(), Macro::object_like(body));
+        self
+    }
+
+    fn warn(&mut self, warning: Warning) {
+        self.warnings
+            .extend(self.warning_options.diagnose(&warning));
+    }
+
+    fn extension(&mut self, message: String) {
+        self.warn(Warning::Extension {
+            message,
+            line: self.location.line,
+        });
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    // whether whitespace came before the token handed out last; always false
+    // unless the tokens came from `lex_with_spacing`.
+    pub fn space_before(&self) -> bool {
+        self.space_before
+    }
+
+    // the state the `#pragma STDC`s read so far have selected.
+    pub fn stdc(&self) -> &StdcState {
+        &self.stdc
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn macros(&self) -> impl Iterator<Item = (&BStr, &Macro<'a>)> {
+        self.macros
+            .iter()
+            .map(|(name, definition)| (name.as_ref(), definition))
+    }
+
+    pub fn macro_index(&self) -> &MacroIndex {
+        &self.macro_index
+    }
+
+    pub fn include_graph(&self) -> &IncludeGraph {
+        &self.include_graph
+    }
+
+    // the include graph, with the headers the file provider finds followed
+    // in turn.
+    pub fn full_include_graph(&self) -> IncludeGraph {
+        let mut graph = self.include_graph.clone();
+        graph.follow(&*self.files, &self.options);
+        graph
+    }
+
+    pub fn coverage(&self) -> &CoverageReport {
+        &self.coverage
+    }
+
+    fn site(&self) -> Site {
+        Site {
+            file: self.location.file.clone(),
+            line: self.location.line,
         }
     }
 
     fn handle_iflike_directive(&mut self, directive: Directive) -> Result<(), ParseError> {
         match directive {
-            Directive::If => {
-                self.parse_condition()?;
+            Directive::If | Directive::Ifdef | Directive::Ifndef => {
+                let parent_active = !self.skipping();
+                let result = if parent_active {
+                    self.evaluate_group(directive)
+                } else {
+                    self.collect_line();
+                    Ok(Some(false))
+                };
+                let condition = *result.as_ref().unwrap_or(&Some(false));
+                let active = condition == Some(true);
+                let parent_unknown = self.conditionals.last().is_some_and(|top| top.unknown);
+                self.conditionals.push(Conditional {
+                    directive,
+                    parent_active,
+                    taken: active,
+                    active,
+                    unknown: parent_unknown || condition.is_none(),
+                });
+                self.record_group(directive, parent_active, active);
+                let depth = self.conditionals.len();
+                if parent_active && depth == self.limits.conditional_nesting + 1 {
+                    let max = self.limits.conditional_nesting;
+                    self.limit_exceeded("conditional nesting depth", depth, max);
+                }
+                result?;
             }
             Directive::Elif => {
-                let top = self.directives.last().ok_or(ParseError::MismatchedElif)?;
-                if !matches!(
-                    top,
-                    Directive::If | Directive::Ifdef | Directive::Ifndef | Directive::Elif
-                ) {
+                let top = self.conditionals.last().ok_or(ParseError::MismatchedElif)?;
+                if top.directive == Directive::Else {
                     return Err(ParseError::MismatchedElif);
                 }
+                let evaluated = top.parent_active && !top.taken;
+                let result = if evaluated {
+                    self.evaluate_group(directive)
+                } else {
+                    self.collect_line();
+                    Ok(Some(false))
+                };
+                let condition = *result.as_ref().unwrap_or(&Some(false));
+                let top = self.conditionals.last_mut().unwrap();
+                // after an unknown condition, whether this group is reached
+                // isn't known either
+                let active = condition == Some(true) && !top.unknown;
+                top.directive = directive;
+                top.active = active;
+                top.taken |= active;
+                top.unknown |= condition.is_none();
+                self.record_group(directive, evaluated, active);
+                result?;
             }
-            Directive::Ifdef | Directive::Ifndef => {}
             _ => unreachable!(),
         }
         Ok(())
     }
-}
 
-impl<'a, Tokens> Iterator for Parser<'a, Tokens>
-where
-    Tokens: Iterator<Item = Token<'a>>,
-{
-    type Item = Result<Token<'a>, ParseError>;
+    fn handle_else(&mut self) -> Result<(), ParseError> {
+        self.collect_line();
+        let top = self
+            .conditionals
+            .last_mut()
+            .ok_or(ParseError::MismatchedElse)?;
+        if top.directive == Directive::Else {
+            return Err(ParseError::ElseAfterElse);
+        }
+        let reached = top.parent_active && !top.taken;
+        let active = reached && !top.unknown;
+        top.directive = Directive::Else;
+        top.active = active;
+        top.taken = true;
+        self.record_group(Directive::Else, reached, active);
+        Ok(())
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(tok) = self.out_stack.pop_front() {
-            return Some(Ok(tok));
+    fn handle_endif(&mut self) -> Result<(), ParseError> {
+        if !self.collect_line().is_empty() {
+            self.extension("extra tokens at end of `#endif`".to_owned());
         }
+        self.conditionals.pop().ok_or(ParseError::MismatchedEndif)?;
+        Ok(())
+    }
 
-        match self.tokens.next()? {
-            Token::Punct(Punct::Hash) => {
-                // this next word should be one of the known directives
-                match self.tokens.next() {
-                    Some(Token::Ident(id)) => match id.as_bytes() {
-                        b"if" => {
-                            self.handle_iflike_directive(Directive::If)?;
-                        }
-                        b"ifdef" => {
-                            self.handle_iflike_directive(Directive::Ifdef)?;
-                        }
-                        b"ifndef" => {
-                            self.handle_iflike_directive(Directive::Ifndef)?;
-                        }
-                        b"elif" => {
-                            self.handle_iflike_directive(Directive::Elif)?;
-                        }
-                        b"else" => {
-                            self.handle_else()?;
-                        }
-                        b"endif" => {
-                            self.handle_endif()?;
-                        }
-                        b"include" => {
-                            self.handle_include()?;
-                        }
-                        b"define" => {
-                            self.handle_define()?;
-                        }
-                        b"undef" => {
-                            self.handle_undef()?;
-                        }
-                        b"line" => {
-                            self.handle_line()?;
-                        }
-                        b"error" => {
-                            self.handle_error()?;
-                        }
-                        b"pragma" => {
-                            self.handle_pragma()?;
-                        }
-                        _ => return Some(Err(ParseError::InvalidDirective(id.to_owned()))),
-                    },
-                    Some(tok) => {
-                        return Some(Err(ParseError::InvalidDirective(
-                            format!("{tok}").into_bytes().into(),
-                        )))
+    // whether the current group is being skipped.
+    fn skipping(&self) -> bool {
+        self.conditionals
+            .last()
+            .is_some_and(|conditional| !conditional.active)
+    }
+
+    // whether the group is entered, or `None` if that can't be known.
+    fn evaluate_group(&mut self, directive: Directive) -> Result<Option<bool>, ParseError> {
+        match directive {
+            Directive::Ifdef | Directive::Ifndef => {
+                let line = self.collect_line();
+                let name = macro_name(&line)?;
+                self.macro_index.record_use(name, self.site());
+                let defined = self.macros.contains_key(name);
+                Ok(Some(defined == (directive == Directive::Ifdef)))
+            }
+            _ => self.parse_condition(),
+        }
+    }
+
+    fn parse_condition(&mut self) -> Result<Option<bool>, ParseError> {
+        let line = self.collect_line();
+        let boolean_literals =
+            self.options.cplusplus.is_some() || self.options.c_standard >= CStandard::C23;
+        if boolean_literals {
+            self.check_boolean_macros(&line);
+        }
+        self.check_expansion_to_defined(&line);
+        let Some(expanded) = self.expand_object_like(&line) else {
+            return Ok(None);
+        };
+        let macros = &self.macros;
+        let is_asserted =
+            |predicate: &BStr, answer: Option<&BStr>| self.is_asserted(predicate, answer);
+        let mut evaluator = Evaluator::new(&expanded, |name| macros.contains_key(name))
+            .with_target(self.options.target);
+        if self.options.gnu_extensions {
+            evaluator = evaluator.with_assertions(&is_asserted);
+        }
+        if boolean_literals {
+            evaluator = evaluator.with_boolean_literals();
+        }
+        let value = evaluator.evaluate()?;
+        Ok(Some(value != 0))
+    }
+
+    // `line` with its object-like macros expanded, or `None` if that would
+    // take a function-like macro.
+    fn expand_object_like(&mut self, line: &[Token<'a>]) -> Option<Vec<Token<'a>>> {
+        let mut expanded = vec![];
+        let mut expansions = vec![];
+        let expandable = self.expand_condition(line, &mut vec![], &mut expanded, &mut expansions);
+        for name in expansions {
+            self.stats.record_directive_expansion(name);
+            self.macro_index.record_use(name, self.site());
+        }
+        for name in defined_operands(&expanded) {
+            self.macro_index.record_use(name, self.site());
+        }
+        expandable.then_some(expanded)
+    }
+
+    // replace the object-like macros in `tokens`, other than the operands of
+    // `defined` and those in `expanding`, by their replacement lists, in
+    // turn expanded, adding the name of each to `expansions`. false if a
+    // function-like macro is invoked, which is still added, or a `##` would
+    // paste, which can't be done yet.
+    fn expand_condition(
+        &self,
+        tokens: &[Token<'a>],
+        expanding: &mut Vec<&'a BStr>,
+        out: &mut Vec<Token<'a>>,
+        expansions: &mut Vec<&'a BStr>,
+    ) -> bool {
+        // the next identifier is the operand of `defined`
+        let mut operand = false;
+        for (i, &tok) in tokens.iter().enumerate() {
+            let after_defined = std::mem::take(&mut operand);
+            out.push(tok);
+            let name = match tok {
+                Token::Ident(name) if name == "defined" => {
+                    operand = true;
+                    continue;
+                }
+                Token::Punct(Punct::LParen) if after_defined => {
+                    operand = true;
+                    continue;
+                }
+                Token::Ident(name) if !after_defined && !expanding.contains(&name) => name,
+                _ => continue,
+            };
+            let Some(definition) = self.macros.get(name) else {
+                continue;
+            };
+            if definition.is_function_like() {
+                // a name not followed by `(` is no invocation; at the end of
+                // a replacement list, the `(` could come after it
+                match tokens.get(i + 1) {
+                    Some(Token::Punct(Punct::LParen)) => {}
+                    None if !expanding.is_empty() => {}
+                    _ => continue,
+                }
+                expansions.push(name);
+                return false;
+            }
+            let mut body = Vec::with_capacity(definition.body.len());
+            for tok in &definition.body {
+                match tok {
+                    BodyToken::Token(tok) => body.push(*tok),
+                    _ => return false,
+                }
+            }
+            out.pop();
+            expansions.push(name);
+            expanding.push(name);
+            let expanded = self.expand_condition(&body, expanding, out, expansions);
+            expanding.pop();
+            if !expanded {
+                return false;
+            }
+        }
+        true
+    }
+
+    // `true` and `false` defined as anything but `1` and `0`, which #if
+    // expands where the keywords were likely meant.
+    fn check_boolean_macros(&mut self, line: &[Token<'a>]) {
+        for (name, value) in [("true", "1"), ("false", "0")] {
+            let used = line.contains(&Token::Ident(BStr::new(name)));
+            let value = [BodyToken::Token(Token::Number(BStr::new(value)))];
+            let redefined = self
+                .macros
+                .get(BStr::new(name))
+                .is_some_and(|definition| definition.body != value);
+            if used && redefined {
+                self.warn(Warning::KeywordMacro {
+                    name: name.into(),
+                    line: self.location.line,
+                });
+            }
+        }
+    }
+
+    // macros in a controlling expression whose expansion would produce
+    // `defined`. GCC and Clang take it as the operator all the same.
+    fn check_expansion_to_defined(&mut self, line: &[Token<'a>]) {
+        // the next identifier is the operand of `defined`
+        let mut operand = false;
+        for tok in line {
+            match tok {
+                Token::Ident(name) if *name == "defined" => {
+                    operand = true;
+                    continue;
+                }
+                Token::Punct(Punct::LParen) if operand => continue,
+                Token::Ident(name) if !operand && self.expands_to_defined(name) => {
+                    self.warn(Warning::ExpansionToDefined {
+                        name: (*name).to_owned(),
+                        line: self.location.line,
+                    });
+                }
+                _ => {}
+            }
+            operand = false;
+        }
+    }
+
+    // whether `defined` is in the replacement list of `name`, or of a macro
+    // named in it.
+    fn expands_to_defined(&self, name: &BStr) -> bool {
+        let mut pending = vec![name];
+        let mut seen = vec![];
+        while let Some(name) = pending.pop() {
+            if seen.contains(&name) {
+                continue;
+            }
+            seen.push(name);
+            let Some(definition) = self.macros.get(name) else {
+                continue;
+            };
+            for tok in &definition.body {
+                match tok {
+                    BodyToken::Token(Token::Ident(ident)) if *ident == "defined" => return true,
+                    BodyToken::Token(Token::Ident(ident)) => pending.push(ident),
+                    _ => {}
+                }
+            }
+        }
+        false
+    }
+
+    fn record_group(&mut self, directive: Directive, evaluated: bool, active: bool) {
+        let unknown = self.conditionals.last().is_some_and(|top| top.unknown);
+        let state = match (evaluated, active) {
+            (true, _) | (_, true) if unknown => GroupState::Unknown,
+            (_, true) => GroupState::Entered,
+            (true, false) => GroupState::Skipped,
+            (false, false) => GroupState::NotEvaluated,
+        };
+        self.stats.conditional_groups += 1;
+        if state != GroupState::Entered {
+            self.stats.skipped_branches += 1;
+        }
+        self.coverage.push(Group {
+            directive: directive.name(),
+            file: self.location.file.clone(),
+            line: self.location.line,
+            state,
+        });
+    }
+
+    fn handle_assert(&mut self) -> Result<(), ParseError> {
+        let (predicate, answer) = self.parse_assertion()?;
+        let answer = answer.ok_or(ParseError::MissingAnswer)?;
+        let answers = self.assertions.entry(predicate).or_default();
+        if !answers.contains(&answer) {
+            answers.push(answer);
+        }
+        Ok(())
+    }
+
+    fn handle_unassert(&mut self) -> Result<(), ParseError> {
+        match self.parse_assertion()? {
+            (predicate, Some(answer)) => {
+                if let Some(answers) = self.assertions.get_mut(&predicate) {
+                    answers.retain(|a| *a != answer);
+                }
+            }
+            (predicate, None) => {
+                self.assertions.remove(&predicate);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_error(&mut self) -> Result<(), ParseError> {
+        let message = self
+            .collect_line()
+            .iter()
+            .map(|tok| tok.spelling())
+            .join(" ");
+        Err(ParseError::ErrorDirective(message.into()))
+    }
+
+    fn handle_ident(&mut self) -> Result<(), ParseError> {
+        match self.collect_line().as_slice() {
+            [Token::StringLit(text)] if text.starts_with(b"\"") => {
+                self.callbacks.ident(text);
+                Ok(())
+            }
+            _ => Err(ParseError::InvalidIdent),
+        }
+    }
+
+    fn handle_line(&mut self) -> Result<(), ParseError> {
+        let operands = self.collect_line();
+        // the operands are macro-expanded; what can't be is left as written
+        let operands = self.expand_object_like(&operands).unwrap_or(operands);
+        let (line, file) = match operands.as_slice() {
+            [] => return Err(ParseError::MissingLineNumber),
+            [Token::Number(line)] => (parse_line_directive_number(line)?, None),
+            [Token::Number(line), file] => (
+                parse_line_directive_number(line)?,
+                Some(parse_file_name(*file)?),
+            ),
+            [Token::Number(_), _, ..] => return Err(ParseError::ExtraTokensAfterLine),
+            [tok, ..] => return Err(ParseError::InvalidLineNumber(tok.spelling().to_owned())),
+        };
+
+        self.location.line = line - 1;
+        if file.is_some() {
+            self.location.file = file;
+        }
+        Ok(())
+    }
+
+    // `# 33 "file.c" 1 3`, as found in the output of `cpp -E`.
+    fn handle_linemarker(&mut self, line: &BStr) -> Result<(), ParseError> {
+        let line = parse_line_number(line)?;
+        let operands = self.collect_line();
+        let mut operands = operands.into_iter();
+        let file = match operands.next() {
+            Some(tok) => Some(parse_file_name(tok)?),
+            None => None,
+        };
+        let mut system_header = false;
+        for flag in operands {
+            match flag.spelling().as_bytes() {
+                b"1" | b"2" | b"4" => {}
+                b"3" => system_header = true,
+                _ => {
+                    return Err(ParseError::InvalidLinemarkerFlag(
+                        flag.spelling().to_owned(),
+                    ))
+                }
+            }
+        }
+
+        // the marker names the line that follows it. `# 0`, which GCC starts
+        // its output with, wraps round to 0 at the line break.
+        self.location.line = line.wrapping_sub(1);
+        if file.is_some() {
+            self.location.file = file;
+        }
+        self.location.system_header = system_header;
+        Ok(())
+    }
+
+    fn handle_pragma(&mut self) -> Result<(), ParseError> {
+        let spaced = self.collect_spaced_line();
+        let line: Vec<_> = spaced.iter().map(|&(tok, _)| tok).collect();
+        let pragma = Pragma::parse(line.clone())?;
+        match &pragma {
+            Pragma::Stdc(kind, switch) => self.stdc.set(*kind, *switch),
+            Pragma::FenvRound(direction) => self.stdc.fenv_round = *direction,
+            Pragma::FenvDecRound(direction) => self.stdc.fenv_dec_round = *direction,
+            Pragma::Diagnostic(diagnostic) => self.apply_diagnostic_pragma(diagnostic),
+            Pragma::Message(text) => self.warn(Warning::PragmaMessage {
+                text: text.clone(),
+                line: self.location.line,
+            }),
+            _ => {}
+        }
+        let claimed = self.callbacks.pragma(&pragma);
+        if !claimed && !pragma.is_builtin() {
+            // an unknown `STDC` pragma is named with its second word
+            let words = match line.as_slice() {
+                [Token::Ident(stdc), _, ..] if *stdc == "STDC" => 2,
+                _ => 1,
+            };
+            let name = line.iter().take(words).map(|tok| tok.spelling()).join(" ");
+            self.warn(Warning::UnknownPragma {
+                name: name.into(),
+                line: self.location.line,
+            });
+        }
+        if pragma.is_region() && self.output_options.keep_region_pragmas {
+            self.pass_through("pragma", spaced);
+        }
+        Ok(())
+    }
+
+    fn apply_diagnostic_pragma(&mut self, pragma: &DiagnosticPragma) {
+        let (option, action): (_, fn(&mut WarningOptions, WarningFlag)) = match pragma {
+            DiagnosticPragma::Push => {
+                self.warning_stack.push(self.warning_options.clone());
+                return;
+            }
+            DiagnosticPragma::Pop => {
+                if let Some(options) = self.warning_stack.pop() {
+                    self.warning_options = options;
+                }
+                return;
+            }
+            DiagnosticPragma::Ignored(option) => (option, WarningOptions::disable),
+            DiagnosticPragma::Warning(option) => (option, WarningOptions::enable),
+            DiagnosticPragma::Error(option) => (option, WarningOptions::promote),
+        };
+        // options for warnings this crate doesn't emit are ignored
+        if let Some(flag) = option.to_str().ok().and_then(WarningFlag::from_name) {
+            action(&mut self.warning_options, flag);
+        }
+    }
+
+    fn handle_define(&mut self) -> Result<(), ParseError> {
+        let mut spaced = self.collect_spaced_line();
+        let line: Vec<_> = spaced.iter().map(|&(tok, _)| tok).collect();
+        let name = macro_name(&line)?;
+        let comma_deletion = line.windows(3).any(|window| {
+            matches!(
+                window,
+                [Token::Punct(Punct::Comma), Token::Punct(Punct::HashHash), Token::Ident(va_args)]
+                    if *va_args == "__VA_ARGS__"
+            )
+        });
+        if comma_deletion {
+            self.extension("`, ## __VA_ARGS__` is a GNU extension".to_owned());
+        }
+        let function_like = self.opens_parameter_list(&line);
+        let definition = Macro::parse(&line[1..], function_like)?;
+        let last_param = definition.params.as_ref().and_then(|params| params.last());
+        if definition.variadic && last_param.is_some_and(|param| *param != "__VA_ARGS__") {
+            self.extension("named variadic macro parameters are a GNU extension".to_owned());
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            %name,
+            body = %line[1..].iter().map(|tok| tok.spelling()).join(" "),
+            line = self.location.line,
+            "define"
+        );
+        if let Some(e) = invalid_paste(&definition, &self.options) {
+            return Err(e);
+        }
+        let parameters = definition.params.as_ref().map_or(0, Vec::len);
+        if parameters > self.limits.macro_parameters {
+            let max = self.limits.macro_parameters;
+            self.limit_exceeded("number of macro parameters", parameters, max);
+        }
+        let new = self.macros.insert(name.to_owned(), definition).is_none();
+        if new && self.macros.len() == self.limits.macro_definitions + 1 {
+            let max = self.limits.macro_definitions;
+            self.limit_exceeded("number of defined macros", self.macros.len(), max);
+        }
+        self.macro_index.record_definition(name, self.site());
+        if self.output_options.keep_defines {
+            // `NAME (` must stay spaced, or it would read back function-like
+            if let [_, (Token::Punct(Punct::LParen), space_before), ..] = &mut spaced[..] {
+                *space_before |= !function_like;
+            }
+            self.pass_through("define", spaced);
+        }
+        Ok(())
+    }
+
+    // follow the parentheses of a function-like macro invocation, whose
+    // arguments may span lines.
+    fn track_invocation(&mut self, line: &[Token<'a>]) {
+        let mut tokens = line.iter().peekable();
+        while let Some(tok) = tokens.next() {
+            let Some(invocation) = &mut self.invocation else {
+                if let Token::Ident(name) = tok {
+                    if tokens.peek() == Some(&&Token::Punct(Punct::LParen))
+                        && self.macros.get(*name).is_some_and(Macro::is_function_like)
+                    {
+                        self.invocation = Some(Invocation {
+                            name: (*name).to_owned(),
+                            line: self.location.line,
+                            depth: 0,
+                            commas: 0,
+                            empty: true,
+                        });
+                    }
+                }
+                continue;
+            };
+            match tok {
+                Token::Punct(Punct::LParen) if invocation.depth == 0 => {
+                    invocation.depth = 1;
+                    continue;
+                }
+                Token::Punct(Punct::LParen) => invocation.depth += 1,
+                Token::Punct(Punct::RParen) if invocation.depth == 1 => {
+                    let arguments = match invocation {
+                        Invocation { empty: true, .. } => 0,
+                        Invocation { commas, .. } => *commas + 1,
+                    };
+                    self.invocation = None;
+                    if arguments > self.limits.macro_arguments {
+                        let max = self.limits.macro_arguments;
+                        self.limit_exceeded("number of macro arguments", arguments, max);
+                    }
+                    continue;
+                }
+                Token::Punct(Punct::RParen) => invocation.depth -= 1,
+                Token::Punct(Punct::Comma) if invocation.depth == 1 => invocation.commas += 1,
+                _ => {}
+            }
+            invocation.empty = false;
+        }
+    }
+
+    fn directive_in_arguments(&mut self, directive: &BStr) {
+        let Some(Invocation { name, line, .. }) = &self.invocation else {
+            return;
+        };
+        let warning = Warning::DirectiveInMacroArguments {
+            directive: directive.to_owned(),
+            name: name.clone(),
+            open_line: *line,
+            line: self.location.line,
+        };
+        self.warn(warning);
+    }
+
+    // whether the `(` after the macro name in `line` starts a parameter list,
+    // which it does only with no space before it.
+    fn opens_parameter_list(&self, line: &[Token<'a>]) -> bool {
+        let [name, Token::Punct(Punct::LParen), ..] = line else {
+            return false;
+        };
+        let Some(text) = self.text else {
+            return true;
+        };
+        let end = name
+            .offset_in(text)
+            .map(|start| start + name.spelling().len());
+        end.and_then(|end| text.get(end)) == Some(&b'(')
+    }
+
+    fn handle_undef(&mut self) -> Result<(), ParseError> {
+        let spaced = self.collect_spaced_line();
+        let line: Vec<_> = spaced.iter().map(|&(tok, _)| tok).collect();
+        let name = macro_name(&line)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(%name, line = self.location.line, "undef");
+        self.macros.remove(name);
+        self.macro_index.record_use(name, self.site());
+        if self.output_options.keep_defines {
+            self.pass_through("undef", spaced);
+        }
+        Ok(())
+    }
+
+    // headers are not read yet; the inclusion is only recorded.
+    fn handle_include(&mut self) -> Result<(), ParseError> {
+        let line = self.collect_line();
+        let spelling = match line.as_slice() {
+            [Token::StringLit(spelling)] if is_header_name(spelling) => (*spelling).to_owned(),
+            _ => self.computed_header_name(&line)?,
+        };
+        let spelling = spelling.as_bstr();
+        let name = spelling[1..spelling.len() - 1].as_bstr();
+        let from = self
+            .location
+            .file
+            .clone()
+            .unwrap_or_else(|| BString::from("<stdin>"));
+        let path = self.files.resolve(name, spelling[0] == b'"', from.as_ref());
+        let to = path.as_ref().map_or(name, |path| path.as_bstr());
+        self.include_graph.record(
+            from.as_ref(),
+            to,
+            path.is_some(),
+            self.location.line,
+            spelling,
+        );
+        self.stats.include_directives += 1;
+        Ok(())
+    }
+
+    // the operands of `#include MACRO`, expanded and read as a header name.
+    // between `<` and `>`, the tokens are joined without their spacing.
+    fn computed_header_name(&mut self, line: &[Token<'a>]) -> Result<BString, ParseError> {
+        let expanded = self
+            .expand_object_like(line)
+            .ok_or(ParseError::InvalidInclude)?;
+        match expanded.as_slice() {
+            [Token::StringLit(spelling)] if spelling.starts_with(b"\"") => {
+                Ok((*spelling).to_owned())
+            }
+            [Token::Punct(Punct::Lt), inner @ .., Token::Punct(Punct::Gt)] => {
+                let mut spelling = BString::from("<");
+                for tok in inner {
+                    spelling.extend_from_slice(tok.spelling());
+                }
+                spelling.push(b'>');
+                Ok(spelling)
+            }
+            _ => Err(ParseError::InvalidInclude),
+        }
+    }
+
+    // re-emit a directive that has already been consumed.
+    fn pass_through(&mut self, directive: &'static str, line: Vec<(Token<'a>, bool)>) {
+        self.out_stack.push_back((Token::Punct(Punct::Hash), false));
+        self.out_stack
+            .push_back((Token::Ident(BStr::new(directive)), false));
+        self.out_stack.extend(line);
+    }
+
+    // `export module ...;`, `module ...;` and `import ...;` lines get their
+    // leading keywords turned into `ModuleKeyword`s.
+    fn mark_module_directive(&mut self, line: &mut [Token<'a>]) {
+        let keywords = match line {
+            [Token::Ident(export), Token::Ident(kw), rest @ ..]
+                if export.as_bytes() == b"export"
+                    && matches!(kw.as_bytes(), b"module" | b"import") =>
+            {
+                2 * starts_module_operand(rest.first()) as usize
+            }
+            [Token::Ident(kw), rest @ ..] if matches!(kw.as_bytes(), b"module" | b"import") => {
+                starts_module_operand(rest.first()) as usize
+            }
+            _ => 0,
+        };
+        if keywords == 0 {
+            return;
+        }
+        for tok in &mut line[..keywords] {
+            *tok = Token::ModuleKeyword(tok.spelling());
+        }
+        self.callbacks.module_directive(line);
+    }
+
+    // `pred` or `pred(answer)`, with the answer normalized to single spaces
+    // between its tokens.
+    fn parse_assertion(&mut self) -> Result<(BString, Option<BString>), ParseError> {
+        let line = self.collect_line();
+        let mut tokens = line.into_iter();
+        let predicate = match tokens.next() {
+            Some(Token::Ident(id)) => id.to_owned(),
+            _ => return Err(ParseError::MissingPredicate),
+        };
+        match tokens.next() {
+            None => return Ok((predicate, None)),
+            Some(Token::Punct(Punct::LParen)) => {}
+            Some(_) => return Err(ParseError::ExtraTokensAfterAssertion),
+        }
+
+        let mut answer = BString::from(vec![]);
+        let mut depth = 0usize;
+        while let Some(tok) = tokens.next() {
+            match tok {
+                Token::Punct(Punct::RParen) if depth == 0 => {
+                    if tokens.next().is_some() {
+                        return Err(ParseError::ExtraTokensAfterAssertion);
+                    }
+                    if answer.is_empty() {
+                        return Err(ParseError::MissingAnswer);
+                    }
+                    return Ok((predicate, Some(answer)));
+                }
+                Token::Punct(Punct::RParen) => depth -= 1,
+                Token::Punct(Punct::LParen) => depth += 1,
+                _ => {}
+            }
+            if !answer.is_empty() {
+                answer.push(b' ');
+            }
+            answer.extend_from_slice(tok.spelling());
+        }
+        Err(ParseError::UnterminatedAnswer)
+    }
+
+    // `#pred(answer)` in a controlling expression.
+    fn is_asserted(&self, predicate: &BStr, answer: Option<&BStr>) -> bool {
+        match (self.assertions.get(predicate), answer) {
+            (Some(answers), Some(answer)) => answers.iter().any(|a| a == answer),
+            (Some(answers), None) => !answers.is_empty(),
+            (None, _) => false,
+        }
+    }
+
+    // the remaining tokens of the current line, leaving the `Eol` in place.
+    fn collect_line(&mut self) -> Vec<Token<'a>> {
+        self.collect_spaced_line()
+            .into_iter()
+            .map(|(tok, _)| tok)
+            .collect()
+    }
+
+    // like `collect_line`, keeping whether whitespace preceded each token.
+    fn collect_spaced_line(&mut self) -> Vec<(Token<'a>, bool)> {
+        let mut line = vec![];
+        loop {
+            match self.tokens.peek().map(|lexed| lexed.token()) {
+                Some(Token::Eol | Token::Eof) | None => break,
+                _ => {
+                    let tok = self.advance().unwrap();
+                    line.push((tok, self.space_before_read));
+                }
+            }
+        }
+        self.tokens.reset_peek();
+        line
+    }
+
+    fn handle_directive(&mut self, directive: Directive) -> Result<(), ParseError> {
+        match directive {
+            Directive::If | Directive::Ifdef | Directive::Ifndef | Directive::Elif => {
+                self.handle_iflike_directive(directive)
+            }
+            Directive::Else => self.handle_else(),
+            Directive::Endif => self.handle_endif(),
+            Directive::Include => self.handle_include(),
+            Directive::IncludeNext => {
+                self.extension("`#include_next` is a GNU extension".to_owned());
+                self.handle_include()
+            }
+            Directive::Define => self.handle_define(),
+            Directive::Undef => self.handle_undef(),
+            Directive::Line => self.handle_line(),
+            Directive::Error => self.handle_error(),
+            Directive::Pragma => self.handle_pragma(),
+            Directive::Ident => self.handle_ident(),
+            Directive::Assert => self.handle_assert(),
+            Directive::Unassert => self.handle_unassert(),
+        }
+    }
+
+    fn handle_extension(&mut self, name: &BStr, index: usize) -> Result<(), ParseError> {
+        let line = self.collect_line();
+        self.extensions[index]
+            .handle(&line)
+            .map_err(|message| ParseError::ExtensionDirective(name.to_owned(), message))
+    }
+
+    // the rest of a line starting with `#`.
+    fn step_directive(&mut self) -> Result<Option<Token<'a>>, ParseError> {
+        match self.advance() {
+            Some(Token::Ident(id)) => {
+                if self.invocation.is_some() {
+                    self.directive_in_arguments(id);
+                }
+                match self.directives.get(id).copied() {
+                    Some(Handler::Builtin(directive))
+                        if !self.skipping() || directive.is_conditional() =>
+                    {
+                        self.handle_directive(directive)?
                     }
-                    None => return Some(Err(ParseError::MissingDirectiveName)),
+                    Some(Handler::Extension(index)) if !self.skipping() => {
+                        self.handle_extension(id, index)?
+                    }
+                    // only conditionals matter inside a skipped group
+                    Some(_) => {
+                        self.collect_line();
+                    }
+                    None if self.skipping() => {
+                        self.collect_line();
+                    }
+                    None => return Err(ParseError::InvalidDirective(id.to_owned())),
                 }
             }
+            // null directive
+            Some(Token::Eol) => return Ok(Some(Token::Eol)),
+            Some(Token::Eof) => return self.end_of_input(),
+            _ if self.skipping() => {
+                self.collect_line();
+            }
+            Some(Token::Number(line)) => self.handle_linemarker(line)?,
+            Some(tok) => {
+                return Err(ParseError::InvalidDirective(
+                    format!("{tok}").into_bytes().into(),
+                ))
+            }
+            None => return Err(ParseError::MissingDirectiveName),
+        }
+        Ok(None)
+    }
+
+    // the next token to hand out, or `None` when a directive produced nothing.
+    fn step(&mut self) -> Result<Option<Token<'a>>, ParseError> {
+        self.space_before = false;
+        if let Some((tok, space_before)) = self.out_stack.pop_front() {
+            self.space_before = space_before;
+            return Ok(Some(tok));
+        }
+
+        match self.advance().unwrap_or(Token::Eof) {
+            Token::Punct(Punct::Hash) => self.step_directive(),
+            Token::Eof => self.end_of_input(),
+            Token::Eol => Ok(Some(Token::Eol)),
+            _ if self.skipping() => {
+                // the line end is still handed out, so line numbers match
+                self.collect_line();
+                Ok(None)
+            }
             result => {
                 // eagerly consume the line
-                loop {
-                    match self.tokens.peek() {
-                        Some(&Token::Eol | &Token::Eof) | None => break,
-                        _ => {
-                            self.out_stack.push_back(self.tokens.next().unwrap());
+                let mut line = vec![result];
+                let mut spacing = vec![self.space_before_read];
+                for (tok, space_before) in self.collect_spaced_line() {
+                    line.push(tok);
+                    spacing.push(space_before);
+                }
+                if self.options.cplusplus >= Some(CxxStandard::Cxx20) {
+                    self.mark_module_directive(&mut line);
+                }
+                self.check_literals(&line);
+                self.track_invocation(&line);
+                for tok in &line {
+                    if let Token::Ident(name) = tok {
+                        if self.macros.contains_key(*name) {
+                            self.macro_index.record_use(name, self.site());
                         }
                     }
                 }
-                return Some(Ok(result));
+                self.out_stack.extend(line.into_iter().zip(spacing));
+                self.step()
+            }
+        }
+    }
+
+    // report malformed escapes in the literals of a line of code.
+    fn check_literals(&mut self, line: &[Token<'a>]) {
+        for (i, tok) in line.iter().enumerate() {
+            let Token::StringLit(spelling) = tok else {
+                continue;
+            };
+            if !spelling.contains(&b'\\') {
+                continue;
+            }
+            // the lexer leaves encoding prefixes as identifiers
+            let prefix = match i.checked_sub(1).map(|i| line[i]) {
+                Some(Token::Ident(prefix))
+                    if matches!(prefix.as_bytes(), b"u8" | b"u" | b"U" | b"L") =>
+                {
+                    prefix.as_bytes()
+                }
+                _ => b"",
+            };
+            let spelling = BString::from([prefix, spelling.as_bytes()].concat());
+            let Some(decoded) = literal::decode(spelling.as_ref(), &self.options.target) else {
+                continue;
+            };
+            // the prefix, where there is one, is where `spelling` starts
+            let start = self
+                .text
+                .and_then(|text| column_in(text, *tok))
+                .map(|column| column - prefix.len());
+            for (span, error) in decoded.errors {
+                self.warn(Warning::InvalidEscape {
+                    error,
+                    literal: spelling.clone(),
+                    column: start.map(|start| (start + span.start) as u32),
+                    span,
+                    line: self.location.line,
+                });
+            }
+        }
+    }
+
+    // every conditional still open is reported before the `Eof`; the token
+    // stream keeps ending, so each call comes back here.
+    fn end_of_input(&mut self) -> Result<Option<Token<'a>>, ParseError> {
+        if let Some(invocation) = self.invocation.take() {
+            return Err(ParseError::UnterminatedInvocation(
+                invocation.name,
+                invocation.line,
+            ));
+        }
+        match self.conditionals.pop() {
+            Some(conditional) => Err(ParseError::UnterminatedConditional(
+                conditional.directive.name(),
+            )),
+            None => {
+                self.finished = true;
+                Ok(Some(Token::Eof))
+            }
+        }
+    }
+}
+
+impl<'a, Tokens> Iterator for Parser<'a, Tokens>
+where
+    Tokens: Iterator,
+    Tokens::Item: Lexed<'a>,
+{
+    type Item = Result<Token<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            match self.step() {
+                Ok(Some(tok)) => {
+                    self.stats.tokens += 1;
+                    return Some(Ok(tok));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    // recover by dropping whatever is left of the line
+                    self.collect_line();
+                    return Some(Err(e));
+                }
             }
         }
         None
     }
 }
+
+// the 1-based column `tok` starts at in `text`, counted in bytes.
+fn column_in(text: &BStr, tok: Token<'_>) -> Option<usize> {
+    let offset = tok.offset_in(text)?;
+    let line_start = text[..offset].rfind_byte(b'\n').map_or(0, |i| i + 1);
+    Some(offset - line_start + 1)
+}
+
+// `"name"` or `<name>`, as the lexer reads them after `#include`.
+fn is_header_name(spelling: &BStr) -> bool {
+    spelling.len() >= 2 && matches!(spelling[0], b'"' | b'<')
+}
+
+// the names `defined` is applied to in a controlling expression.
+fn defined_operands<'a, 'b>(tokens: &'b [Token<'a>]) -> impl Iterator<Item = &'a BStr> + 'b {
+    tokens.iter().enumerate().filter_map(|(i, tok)| {
+        let Token::Ident(name) = tok else {
+            return None;
+        };
+        let before = match tokens[..i] {
+            [.., Token::Ident(defined), Token::Punct(Punct::LParen)] => defined,
+            [.., Token::Ident(defined)] => defined,
+            _ => return None,
+        };
+        (before == "defined" && *name != "defined").then_some(*name)
+    })
+}
+
+fn macro_name<'a>(line: &[Token<'a>]) -> Result<&'a BStr, ParseError> {
+    match line.first() {
+        Some(Token::Ident(name)) if name.as_bytes() != b"defined" => Ok(name),
+        Some(tok) => Err(ParseError::InvalidMacroName(tok.spelling().to_owned())),
+        None => Err(ParseError::MissingMacroName),
+    }
+}
+
+// the first `##` in `definition` whose operands are both fixed tokens, and
+// don't paste to exactly one token. pastes are done left to right, so the
+// left operand may be the result of the one before; operands that come from
+// parameters can only be checked once the macro is expanded.
+fn invalid_paste(definition: &Macro<'_>, options: &LangOptions) -> Option<ParseError> {
+    // the spelling so far, while it is known
+    let mut lhs: Option<BString> = None;
+    let mut pasting = false;
+    for tok in &definition.body {
+        let spelling = match tok {
+            BodyToken::Paste => {
+                pasting = true;
+                continue;
+            }
+            BodyToken::Token(tok) => Some(tok.spelling()),
+            BodyToken::Param(_) | BodyToken::Stringify(_) => None,
+        };
+        lhs = match (std::mem::take(&mut pasting), lhs, spelling) {
+            (true, Some(lhs), Some(rhs)) => {
+                let mut result = lhs.clone();
+                result.extend_from_slice(rhs);
+                let tokens = lex_with_options(result.as_ref(), options.clone())
+                    .filter(|tok| !matches!(tok, Token::Eol | Token::Eof))
+                    .count();
+                if tokens != 1 {
+                    return Some(ParseError::InvalidPaste(lhs, rhs.to_owned(), result));
+                }
+                Some(result)
+            }
+            (true, ..) => None,
+            (false, _, rhs) => rhs.map(BStr::to_owned),
+        };
+    }
+    None
+}
+
+// `module(` and `import::` are ordinary code, not module directives.
+fn starts_module_operand(tok: Option<&Token<'_>>) -> bool {
+    !matches!(
+        tok,
+        None | Some(Token::Punct(Punct::LParen | Punct::ColonColon))
+    )
+}
+
+// a digit sequence no greater than 2147483647. linemarkers may say 0.
+fn parse_line_number(spelling: &BStr) -> Result<u32, ParseError> {
+    spelling
+        .to_str()
+        .ok()
+        .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|s| s.parse().ok())
+        .filter(|&line| line <= 2147483647)
+        .ok_or_else(|| ParseError::InvalidLineNumber(spelling.to_owned()))
+}
+
+// `#line` requires a line number of at least 1.
+fn parse_line_directive_number(spelling: &BStr) -> Result<u32, ParseError> {
+    match parse_line_number(spelling)? {
+        0 => Err(ParseError::InvalidLineNumber(spelling.to_owned())),
+        line => Ok(line),
+    }
+}
+
+fn parse_file_name(tok: Token<'_>) -> Result<BString, ParseError> {
+    match tok {
+        Token::StringLit(v) if v.len() >= 2 && v.starts_with(b"\"") => Ok(v[1..v.len() - 1].into()),
+        tok => Err(ParseError::InvalidFileName(tok.spelling().to_owned())),
+    }
+}
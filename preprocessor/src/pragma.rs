@@ -0,0 +1,228 @@
+use bstr::BString;
+use bstr::ByteSlice;
+use itertools::Itertools;
+
+use crate::parser::ParseError;
+use crate::token::Punct;
+use crate::token::Token;
+
+pub enum Pragma<'a> {
+    // `#pragma region name`, used by editors for folding.
+    Region(Vec<Token<'a>>),
+    // `#pragma endregion comment`
+    EndRegion(Vec<Token<'a>>),
+    // `#pragma STDC FP_CONTRACT ON` and friends.
+    Stdc(StdcPragma, OnOffSwitch),
+    // `#pragma STDC FENV_ROUND FE_UPWARD`
+    FenvRound(RoundingDirection),
+    // `#pragma STDC FENV_DEC_ROUND FE_DEC_UPWARD`, for decimal floating types.
+    FenvDecRound(RoundingDirection),
+    // `#pragma message("text")`, with the text already unquoted.
+    Message(BString),
+    // `#pragma GCC diagnostic ...` or `#pragma clang diagnostic ...`
+    Diagnostic(DiagnosticPragma),
+    // anything else, as the full token line following `pragma`.
+    Other(Vec<Token<'a>>),
+}
+
+impl<'a> Pragma<'a> {
+    pub(crate) fn parse(mut line: Vec<Token<'a>>) -> Result<Self, ParseError> {
+        let kind = match line.first() {
+            Some(Token::Ident(id)) => id.as_bytes(),
+            _ => return Ok(Self::Other(line)),
+        };
+        Ok(match kind {
+            b"region" => Self::Region(line.split_off(1)),
+            b"endregion" => Self::EndRegion(line.split_off(1)),
+            // unknown `STDC` pragmas are left for the caller to warn about
+            b"STDC" => parse_stdc(&line[1..])?.unwrap_or(Self::Other(line)),
+            b"message" => Self::Message(parse_message(&line[1..])?),
+            b"GCC" | b"clang" if is_diagnostic(&line[1..]) => {
+                Self::Diagnostic(parse_diagnostic(&line[2..])?)
+            }
+            _ => Self::Other(line),
+        })
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        !matches!(self, Self::Other(_))
+    }
+
+    pub fn is_region(&self) -> bool {
+        matches!(self, Self::Region(_) | Self::EndRegion(_))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdcPragma {
+    FpContract,
+    FenvAccess,
+    CxLimitedRange,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnOffSwitch {
+    On,
+    Off,
+    #[default]
+    Default,
+}
+
+// the operand of `FENV_ROUND`, or of `FENV_DEC_ROUND` with `FE_DEC_` in place
+// of `FE_`. `Dynamic` uses the mode set at run time, as without the pragma.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoundingDirection {
+    Downward,
+    ToNearest,
+    ToNearestFromZero,
+    TowardZero,
+    Upward,
+    #[default]
+    Dynamic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticPragma {
+    Push,
+    Pop,
+    // the option is given without its `-W`
+    Ignored(BString),
+    Warning(BString),
+    Error(BString),
+}
+
+// the state selected by the most recent `#pragma STDC` of each kind.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdcState {
+    pub fp_contract: OnOffSwitch,
+    pub fenv_access: OnOffSwitch,
+    pub cx_limited_range: OnOffSwitch,
+    pub fenv_round: RoundingDirection,
+    pub fenv_dec_round: RoundingDirection,
+}
+
+impl StdcState {
+    pub(crate) fn set(&mut self, pragma: StdcPragma, switch: OnOffSwitch) {
+        let slot = match pragma {
+            StdcPragma::FpContract => &mut self.fp_contract,
+            StdcPragma::FenvAccess => &mut self.fenv_access,
+            StdcPragma::CxLimitedRange => &mut self.cx_limited_range,
+        };
+        *slot = switch;
+    }
+}
+
+// `None` for a pragma this doesn't know, which isn't an error.
+fn parse_stdc<'a>(operands: &[Token<'a>]) -> Result<Option<Pragma<'a>>, ParseError> {
+    let [Token::Ident(name), rest @ ..] = operands else {
+        return Ok(None);
+    };
+    let pragma = match name.as_bytes() {
+        b"FENV_ROUND" => Pragma::FenvRound(parse_rounding(rest, b"FE_")?),
+        b"FENV_DEC_ROUND" => Pragma::FenvDecRound(parse_rounding(rest, b"FE_DEC_")?),
+        name => match StdcPragma::from_name(name) {
+            Some(pragma) => Pragma::Stdc(pragma, parse_switch(rest)?),
+            None => return Ok(None),
+        },
+    };
+    Ok(Some(pragma))
+}
+
+fn parse_switch(operands: &[Token<'_>]) -> Result<OnOffSwitch, ParseError> {
+    let switch = match operands {
+        [Token::Ident(switch)] => match switch.as_bytes() {
+            b"ON" => Some(OnOffSwitch::On),
+            b"OFF" => Some(OnOffSwitch::Off),
+            b"DEFAULT" => Some(OnOffSwitch::Default),
+            _ => None,
+        },
+        _ => None,
+    };
+    switch.ok_or_else(|| ParseError::InvalidOnOffSwitch(spell(operands)))
+}
+
+fn parse_rounding(operands: &[Token<'_>], prefix: &[u8]) -> Result<RoundingDirection, ParseError> {
+    let direction = match operands {
+        [Token::Ident(direction)] => match direction.strip_prefix(prefix) {
+            Some(b"DOWNWARD") => Some(RoundingDirection::Downward),
+            Some(b"TONEAREST") => Some(RoundingDirection::ToNearest),
+            Some(b"TONEARESTFROMZERO") => Some(RoundingDirection::ToNearestFromZero),
+            Some(b"TOWARDZERO") => Some(RoundingDirection::TowardZero),
+            Some(b"UPWARD") => Some(RoundingDirection::Upward),
+            Some(b"DYNAMIC") => Some(RoundingDirection::Dynamic),
+            _ => None,
+        },
+        _ => None,
+    };
+    direction.ok_or_else(|| ParseError::InvalidRoundingDirection(spell(operands)))
+}
+
+fn is_diagnostic(operands: &[Token<'_>]) -> bool {
+    matches!(operands.first(), Some(Token::Ident(id)) if id.as_bytes() == b"diagnostic")
+}
+
+fn parse_diagnostic(operands: &[Token<'_>]) -> Result<DiagnosticPragma, ParseError> {
+    let invalid = || ParseError::InvalidDiagnosticPragma(spell(operands));
+    let (kind, rest) = match operands {
+        [Token::Ident(kind), rest @ ..] => (kind.as_bytes(), rest),
+        _ => return Err(invalid()),
+    };
+    let option = match rest {
+        [Token::StringLit(option)] => option
+            .strip_prefix(b"\"-W")
+            .and_then(|option| option.strip_suffix(b"\""))
+            .map(BString::from),
+        _ => None,
+    };
+    match (kind, option) {
+        (b"push", _) if rest.is_empty() => Ok(DiagnosticPragma::Push),
+        (b"pop", _) if rest.is_empty() => Ok(DiagnosticPragma::Pop),
+        (b"ignored", Some(option)) => Ok(DiagnosticPragma::Ignored(option)),
+        (b"warning", Some(option)) => Ok(DiagnosticPragma::Warning(option)),
+        (b"error", Some(option)) => Ok(DiagnosticPragma::Error(option)),
+        _ => Err(invalid()),
+    }
+}
+
+// `message("a" "b")` and `message "a"` both print `ab`; anything other
+// than string literals is printed as written.
+fn parse_message(operands: &[Token<'_>]) -> Result<BString, ParseError> {
+    let text = match operands {
+        [Token::Punct(Punct::LParen), text @ .., Token::Punct(Punct::RParen)] => text,
+        [Token::Punct(Punct::LParen), ..] => return Err(ParseError::UnterminatedPragmaMessage),
+        text => text,
+    };
+    if text.is_empty() {
+        return Err(ParseError::EmptyPragmaMessage);
+    }
+    if text
+        .iter()
+        .all(|tok| matches!(tok, Token::StringLit(v) if v.starts_with(b"\"")))
+    {
+        Ok(text
+            .iter()
+            .flat_map(|tok| {
+                let v = tok.spelling();
+                v[1..v.len() - 1].iter().copied()
+            })
+            .collect::<Vec<_>>()
+            .into())
+    } else {
+        Ok(spell(text))
+    }
+}
+
+fn spell(tokens: &[Token<'_>]) -> BString {
+    tokens.iter().map(|tok| tok.spelling()).join(" ").into()
+}
+
+impl StdcPragma {
+    fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"FP_CONTRACT" => Some(Self::FpContract),
+            b"FENV_ACCESS" => Some(Self::FenvAccess),
+            b"CX_LIMITED_RANGE" => Some(Self::CxLimitedRange),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashSet;
+
+use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice;
+use initial::lines::Lines;
+use initial::source_map::SourceMap;
+
+use crate::keyword;
+use crate::lexer::lex_with_spacing;
+use crate::options::LangOptions;
+use crate::token::Punct;
+use crate::token::Token;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticClass {
+    Keyword,
+    MacroName,
+    MacroParameter,
+    HeaderName,
+    String,
+    Number,
+    Comment,
+}
+
+// a classified span of the original source. positions are 0-based physical
+// lines and byte columns; a token continued by a line splice is reported by
+// where it starts. comments are split into one span per line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemanticToken {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+    pub class: SemanticClass,
+}
+
+// classify the keywords, macro names, macro parameters, header names,
+// literals and comments in `source`, in source order.
+pub fn classify(source: &BStr, options: &LangOptions) -> Vec<SemanticToken> {
+    let (text, map) = Lines::new(source)
+        .merge_escaped_newlines()
+        .delete_comments()
+        .finish_with_source_map();
+    let keywords = keyword::keywords(options).collect();
+    let mut classifier = Classifier {
+        text: text.as_ref(),
+        map: &map,
+        keywords,
+        macros: HashSet::new(),
+        out: vec![],
+    };
+    let mut line = vec![];
+    for (tok, space_before) in lex_with_spacing(text.as_ref(), options.clone()) {
+        match tok {
+            Token::Eol | Token::Eof => {
+                classifier.line(&line);
+                line.clear();
+            }
+            _ => line.push((tok, space_before)),
+        }
+    }
+
+    let line_starts = std::iter::once(0)
+        .chain(source.find_iter("\n").map(|i| i + 1))
+        .collect::<Vec<_>>();
+    for range in initial::fused::comments(source) {
+        let mut start = range.start;
+        while start < range.end {
+            let line = line_starts.partition_point(|&line_start| line_start <= start) - 1;
+            let line_end = line_starts
+                .get(line + 1)
+                .map_or(source.len(), |&next| next - 1);
+            let end = range.end.min(line_end);
+            classifier.push_comment(line, start - line_starts[line], end - start);
+            start = line_end + 1;
+        }
+    }
+
+    let mut out = classifier.out;
+    out.sort_by_key(|tok| (tok.line, tok.column));
+    out
+}
+
+struct Classifier<'t> {
+    text: &'t BStr,
+    map: &'t SourceMap,
+    keywords: Vec<&'static str>,
+    macros: HashSet<BString>,
+    out: Vec<SemanticToken>,
+}
+
+impl Classifier<'_> {
+    fn line(&mut self, line: &[(Token<'_>, bool)]) {
+        let directive = match line {
+            [(Token::Punct(Punct::Hash), _), (Token::Ident(name), _), ..] => Some(name.as_bytes()),
+            _ => None,
+        };
+        let rest = match directive {
+            Some(_) => {
+                self.push(line[1].0, SemanticClass::Keyword);
+                &line[2..]
+            }
+            None => line,
+        };
+        match directive {
+            Some(b"define") => self.define(rest),
+            Some(b"undef" | b"ifdef" | b"ifndef") => {
+                if let Some(&(tok @ Token::Ident(name), _)) = rest.first() {
+                    self.push(tok, SemanticClass::MacroName);
+                    if directive == Some(b"undef") {
+                        self.macros.remove(name);
+                    }
+                }
+            }
+            Some(b"include" | b"include_next" | b"import") => {
+                for &(tok, _) in rest {
+                    match tok {
+                        Token::StringLit(_) => self.push(tok, SemanticClass::HeaderName),
+                        _ => self.code(tok, &[]),
+                    }
+                }
+            }
+            Some(b"if" | b"elif") => {
+                let mut after_defined = false;
+                for &(tok, _) in rest {
+                    match tok {
+                        Token::Ident(_) if after_defined => {
+                            self.push(tok, SemanticClass::MacroName);
+                            after_defined = false;
+                        }
+                        Token::Ident(name) if name == "defined" => {
+                            self.push(tok, SemanticClass::Keyword);
+                            after_defined = true;
+                        }
+                        Token::Punct(Punct::LParen) => {}
+                        _ => {
+                            after_defined = false;
+                            self.code(tok, &[]);
+                        }
+                    }
+                }
+            }
+            Some(_) => {}
+            None => {
+                for &(tok, _) in rest {
+                    self.code(tok, &[]);
+                }
+            }
+        }
+    }
+
+    fn define(&mut self, rest: &[(Token<'_>, bool)]) {
+        let Some(&(name @ Token::Ident(spelling), _)) = rest.first() else {
+            return;
+        };
+        self.push(name, SemanticClass::MacroName);
+        self.macros.insert(spelling.to_owned());
+        let mut body = &rest[1..];
+        let mut params = vec![];
+        // function-like only if the `(` directly follows the name
+        if let Some((Token::Punct(Punct::LParen), false)) = body.first() {
+            let close = body
+                .iter()
+                .position(|(tok, _)| matches!(tok, Token::Punct(Punct::RParen)))
+                // an unterminated list runs to the end of the line
+                .unwrap_or(body.len());
+            for &(tok, _) in &body[1..close] {
+                if let Token::Ident(param) = tok {
+                    self.push(tok, SemanticClass::MacroParameter);
+                    params.push(param);
+                }
+            }
+            body = body.get(close + 1..).unwrap_or_default();
+        }
+        for &(tok, _) in body {
+            self.code(tok, &params);
+        }
+    }
+
+    fn code(&mut self, tok: Token<'_>, params: &[&BStr]) {
+        let class = match tok {
+            Token::Ident(name) if params.contains(&name) => SemanticClass::MacroParameter,
+            Token::Ident(name) if self.macros.contains(name) => SemanticClass::MacroName,
+            Token::Ident(name) if self.keywords.iter().any(|kw| name == kw) => {
+                SemanticClass::Keyword
+            }
+            Token::StringLit(_) => SemanticClass::String,
+            Token::Number(_) => SemanticClass::Number,
+            _ => return,
+        };
+        self.push(tok, class);
+    }
+
+    fn push(&mut self, tok: Token<'_>, class: SemanticClass) {
+        let Some(location) = tok
+            .offset_in(self.text)
+            .and_then(|offset| self.map.locate(offset))
+        else {
+            return;
+        };
+        self.out.push(SemanticToken {
+            line: location.line,
+            column: location.column,
+            len: tok.spelling().len(),
+            class,
+        });
+    }
+
+    fn push_comment(&mut self, line: usize, column: usize, len: usize) {
+        self.out.push(SemanticToken {
+            line,
+            column,
+            len,
+            class: SemanticClass::Comment,
+        });
+    }
+}
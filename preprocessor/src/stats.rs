@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bstr::BStr;
+use bstr::BString;
+
+// counters for finding out where preprocessing time goes.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    // `#include` and `#include_next` directives processed, whether or not
+    // the header was found
+    pub include_directives: usize,
+    pub lines: usize,
+    pub tokens: usize,
+    // expansions in `#if`, `#elif` and `#line` lines, by macro; text lines
+    // aren't expanded
+    pub directive_expansions: HashMap<BString, usize>,
+    pub conditional_groups: usize,
+    pub skipped_branches: usize,
+}
+
+impl Stats {
+    pub fn record_directive_expansion(&mut self, name: &BStr) {
+        *self
+            .directive_expansions
+            .entry(name.to_owned())
+            .or_default() += 1;
+    }
+
+    // most expanded first, ties broken by name.
+    pub fn directive_expansions_by_count(&self) -> Vec<(&BStr, usize)> {
+        let mut expansions = self
+            .directive_expansions
+            .iter()
+            .map(|(name, &count)| (name.as_ref(), count))
+            .collect::<Vec<(&BStr, usize)>>();
+        expansions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        expansions
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "include directives:    {}", self.include_directives)?;
+        writeln!(f, "lines:                 {}", self.lines)?;
+        writeln!(f, "tokens:                {}", self.tokens)?;
+        writeln!(f, "conditional groups:    {}", self.conditional_groups)?;
+        writeln!(f, "skipped branches:      {}", self.skipped_branches)?;
+        writeln!(
+            f,
+            "directive expansions:  {}",
+            self.directive_expansions.values().sum::<usize>()
+        )?;
+        for (name, count) in self.directive_expansions_by_count() {
+            writeln!(f, "  {name}: {count}")?;
+        }
+        Ok(())
+    }
+}
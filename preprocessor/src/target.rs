@@ -0,0 +1,182 @@
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub arch: Arch,
+    // `None` for bare metal
+    pub os: Option<Os>,
+    // a full standard library rather than the freestanding headers; sets
+    // `__STDC_HOSTED__`
+    pub hosted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+    Riscv32,
+    Riscv64,
+    Wasm32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    Linux,
+    Windows,
+    MacOs,
+}
+
+// the widths of `int`, `long` and pointers, in that order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataModel {
+    Ilp32,
+    Lp64,
+    Llp64,
+}
+
+impl Default for Target {
+    // x86-64 Linux, whose widths were assumed before there were targets.
+    fn default() -> Self {
+        Self {
+            arch: Arch::X86_64,
+            os: Some(Os::Linux),
+            hosted: true,
+        }
+    }
+}
+
+impl Target {
+    pub fn data_model(&self) -> DataModel {
+        match (self.arch.pointer_width(), self.os) {
+            (32, _) => DataModel::Ilp32,
+            (_, Some(Os::Windows)) => DataModel::Llp64,
+            _ => DataModel::Lp64,
+        }
+    }
+
+    // in bits.
+    pub fn long_width(&self) -> u32 {
+        match self.data_model() {
+            DataModel::Lp64 => 64,
+            DataModel::Ilp32 | DataModel::Llp64 => 32,
+        }
+    }
+
+    // whether plain `char` is signed.
+    pub fn char_signed(&self) -> bool {
+        match self.arch {
+            Arch::Arm | Arch::Aarch64 => matches!(self.os, Some(Os::MacOs | Os::Windows)),
+            Arch::Riscv32 | Arch::Riscv64 => false,
+            Arch::X86 | Arch::X86_64 | Arch::Wasm32 => true,
+        }
+    }
+
+    // the width of `wchar_t` in bits, and whether it is signed.
+    pub fn wchar(&self) -> (u32, bool) {
+        match (self.os, self.arch) {
+            (Some(Os::Windows), _) => (16, false),
+            (Some(Os::MacOs), _) => (32, true),
+            (_, Arch::Arm | Arch::Aarch64) => (32, false),
+            _ => (32, true),
+        }
+    }
+
+    // the macros predefined for this target, with their values.
+    pub fn predefined_macros(&self) -> Vec<(&'static str, &'static str)> {
+        let bytes = |bits: u32| match bits {
+            16 => "2",
+            32 => "4",
+            _ => "8",
+        };
+        let mut macros = vec![
+            ("__STDC_HOSTED__", if self.hosted { "1" } else { "0" }),
+            ("__CHAR_BIT__", "8"),
+            ("__SIZEOF_SHORT__", "2"),
+            ("__SIZEOF_INT__", "4"),
+            ("__SIZEOF_LONG__", bytes(self.long_width())),
+            ("__SIZEOF_LONG_LONG__", "8"),
+            ("__SIZEOF_POINTER__", bytes(self.arch.pointer_width())),
+            ("__SIZEOF_WCHAR_T__", bytes(self.wchar().0)),
+        ];
+        match self.data_model() {
+            DataModel::Lp64 => macros.extend([("__LP64__", "1"), ("_LP64", "1")]),
+            DataModel::Ilp32 => macros.extend([("__ILP32__", "1"), ("_ILP32", "1")]),
+            DataModel::Llp64 => {}
+        }
+        if !self.char_signed() {
+            macros.push(("__CHAR_UNSIGNED__", "1"));
+        }
+        macros.extend(match self.arch {
+            Arch::X86 => &[("__i386__", "1"), ("__i386", "1")][..],
+            Arch::X86_64 => &[
+                ("__x86_64__", "1"),
+                ("__x86_64", "1"),
+                ("__amd64__", "1"),
+                ("__amd64", "1"),
+            ],
+            Arch::Arm => &[("__arm__", "1")],
+            Arch::Aarch64 => &[("__aarch64__", "1")],
+            Arch::Riscv32 => &[("__riscv", "1"), ("__riscv_xlen", "32")],
+            Arch::Riscv64 => &[("__riscv", "1"), ("__riscv_xlen", "64")],
+            Arch::Wasm32 => &[("__wasm__", "1"), ("__wasm32__", "1")],
+        });
+        macros.extend(match self.os {
+            Some(Os::Linux) => &[
+                ("__linux__", "1"),
+                ("__linux", "1"),
+                ("__gnu_linux__", "1"),
+                ("__unix__", "1"),
+                ("__unix", "1"),
+            ][..],
+            Some(Os::Windows) if self.arch.pointer_width() == 64 => {
+                &[("_WIN32", "1"), ("_WIN64", "1")]
+            }
+            Some(Os::Windows) => &[("_WIN32", "1")],
+            Some(Os::MacOs) => &[("__APPLE__", "1"), ("__MACH__", "1")],
+            None => &[],
+        });
+        macros
+    }
+}
+
+impl Arch {
+    pub fn pointer_width(self) -> u32 {
+        match self {
+            Self::X86 | Self::Arm | Self::Riscv32 | Self::Wasm32 => 32,
+            Self::X86_64 | Self::Aarch64 | Self::Riscv64 => 64,
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    // a triple such as `x86_64-linux-gnu` or `arm-none-eabi`. with no known
+    // operating system the target is bare metal and freestanding.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('-');
+        let arch = match parts.next().unwrap_or_default() {
+            "x86_64" | "amd64" => Arch::X86_64,
+            "i386" | "i486" | "i586" | "i686" | "x86" => Arch::X86,
+            "aarch64" | "arm64" => Arch::Aarch64,
+            arch if arch.starts_with("arm") || arch.starts_with("thumb") => Arch::Arm,
+            "riscv32" => Arch::Riscv32,
+            "riscv64" => Arch::Riscv64,
+            "wasm32" => Arch::Wasm32,
+            arch => return Err(format!("unknown architecture `{arch}` in target `{s}`")),
+        };
+        let os = parts.find_map(|part| match part {
+            "linux" => Some(Os::Linux),
+            "windows" | "win32" | "mingw32" => Some(Os::Windows),
+            part if part.starts_with("darwin") || part.starts_with("macos") => Some(Os::MacOs),
+            _ => None,
+        });
+        Ok(Self {
+            arch,
+            os,
+            hosted: os.is_some(),
+        })
+    }
+}
@@ -4,17 +4,22 @@ use bstr::BStr;
 use convert_case::Case;
 use convert_case::Casing;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Token<'a> {
     Ident(&'a BStr),
     StringLit(&'a BStr),
     Number(&'a BStr),
     Punct(Punct),
+    // `module`, `import` or `export` introducing a C++20 module directive.
+    ModuleKeyword(&'a BStr),
+    // an identifier phase 7 converted into a keyword.
+    Keyword(&'a BStr),
     Other(&'a BStr),
     Eol,
     Eof,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Punct {
     Period,
     Arrow,
@@ -66,11 +71,118 @@ pub enum Punct {
     RBrace,
     Semicolon,
     Ellipsis,
+
+    // C++ only
+    ColonColon,
+    PeriodStar,
+    ArrowStar,
+    Spaceship,
 }
 
 impl<'a> Token<'a> {
     pub(crate) fn is_hash(&self) -> bool {
-        return matches!(self, Token::Punct(Punct::Hash));
+        matches!(self, Token::Punct(Punct::Hash))
+    }
+
+    pub fn spelling(&self) -> &'a BStr {
+        match self {
+            Self::Ident(v)
+            | Self::StringLit(v)
+            | Self::Number(v)
+            | Self::ModuleKeyword(v)
+            | Self::Keyword(v)
+            | Self::Other(v) => v,
+            Self::Punct(p) => BStr::new(p.spelling()),
+            Self::Eol | Self::Eof => BStr::new(""),
+        }
+    }
+
+    // where the token's text starts in `text`, if it was lexed from it.
+    // punctuators and line ends don't point into the input.
+    pub(crate) fn offset_in(&self, text: &BStr) -> Option<usize> {
+        if let Self::Punct(_) | Self::Eol | Self::Eof = self {
+            return None;
+        }
+        let spelling = self.spelling();
+        let offset = (spelling.as_ptr() as usize).checked_sub(text.as_ptr() as usize)?;
+        (offset + spelling.len() <= text.len()).then_some(offset)
+    }
+
+    // the same token with its text replaced by `f(text)`.
+    pub fn map_text<'b>(self, f: impl FnOnce(&'a BStr) -> &'b BStr) -> Token<'b> {
+        match self {
+            Self::Ident(v) => Token::Ident(f(v)),
+            Self::StringLit(v) => Token::StringLit(f(v)),
+            Self::Number(v) => Token::Number(f(v)),
+            Self::ModuleKeyword(v) => Token::ModuleKeyword(f(v)),
+            Self::Keyword(v) => Token::Keyword(f(v)),
+            Self::Other(v) => Token::Other(f(v)),
+            Self::Punct(p) => Token::Punct(p),
+            Self::Eol => Token::Eol,
+            Self::Eof => Token::Eof,
+        }
+    }
+}
+
+impl Punct {
+    pub fn spelling(&self) -> &'static str {
+        match self {
+            Self::Period => ".",
+            Self::Arrow => "->",
+            Self::PlusPlus => "++",
+            Self::MinusMinus => "--",
+            Self::Amp => "&",
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Tilde => "~",
+            Self::Bang => "!",
+            Self::Slash => "/",
+            Self::Percent => "%",
+            Self::LtLt => "<<",
+            Self::GtGt => ">>",
+            Self::Lt => "<",
+            Self::Gt => ">",
+            Self::LtEq => "<=",
+            Self::GtEq => ">=",
+            Self::EqEq => "==",
+            Self::BangEq => "!=",
+            Self::Caret => "^",
+            Self::Pipe => "|",
+            Self::AmpAmp => "&&",
+            Self::PipePipe => "||",
+            Self::Question => "?",
+            Self::StarEq => "*=",
+            Self::SlashEq => "/=",
+            Self::PercentEq => "%=",
+            Self::PlusEq => "+=",
+            Self::MinusEq => "-=",
+            Self::LtLtEq => "<<=",
+            Self::GtGtEq => ">>=",
+            Self::AmpEq => "&=",
+            Self::CaretEq => "^=",
+            Self::PipeEq => "|=",
+            Self::HashHash => "##",
+
+            Self::LBrack => "[",
+            Self::RBrack => "]",
+            Self::LParen => "(",
+            Self::RParen => ")",
+            Self::Star => "*",
+            Self::Comma => ",",
+            Self::Colon => ":",
+            Self::Eq => "=",
+            Self::Hash => "#",
+
+            Self::LBrace => "{",
+            Self::RBrace => "}",
+            Self::Semicolon => ";",
+            Self::Ellipsis => "...",
+
+            Self::ColonColon => "::",
+            Self::PeriodStar => ".*",
+            Self::ArrowStar => "->*",
+            Self::Spaceship => "<=>",
+        }
     }
 }
 
@@ -86,6 +198,8 @@ impl<'a> Display for Token<'a> {
                     .to_case(Case::Snake);
                 write!(f, "{{punct .{p}}}")
             }
+            Self::ModuleKeyword(v) => write!(f, "{{module_keyword '{v}'}}"),
+            Self::Keyword(v) => write!(f, "{{keyword '{v}'}}"),
             Self::Other(v) => write!(f, "{{other '{v}'}}"),
             Self::Eol => write!(f, "{{EOL}}"),
             Self::Eof => write!(f, "{{EOF}}"),
@@ -0,0 +1,231 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use bstr::BString;
+
+use crate::diagnostic::Diagnostic;
+use crate::literal::EscapeError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningFlag {
+    UnknownPragmas,
+    PragmaMessages,
+    Normalized,
+    ImplementationLimits,
+    Pedantic,
+    InvalidEscapes,
+    EmbeddedDirective,
+    KeywordMacro,
+    ExpansionToDefined,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Warning {
+    #[error("line {line}: ignoring unknown pragma `{name}`")]
+    UnknownPragma { name: BString, line: u32 },
+    #[error("line {line}: {text}")]
+    PragmaMessage { text: BString, line: u32 },
+    #[error("line {line}: `{ident}` is not in Normalization Form C; did you mean `{normalized}`?")]
+    NotNfc {
+        ident: BString,
+        normalized: BString,
+        line: u32,
+    },
+    #[error("line {line}: {what} is {count}, more than the portable limit of {max}")]
+    LimitExceeded {
+        what: &'static str,
+        count: usize,
+        max: usize,
+        line: u32,
+    },
+    // an extension the standard doesn't allow.
+    #[error("line {line}: {message}")]
+    Extension { message: String, line: u32 },
+    // `span` is where the escape is in `literal`, and `column` where it is
+    // on the line, if the text the literal was lexed from is known.
+    #[error("line {line}{}: {error} in `{literal}`", at_column(.column))]
+    InvalidEscape {
+        error: EscapeError,
+        literal: BString,
+        span: Range<usize>,
+        line: u32,
+        column: Option<u32>,
+    },
+    // `open_line` is where the invocation's `(` is.
+    #[error("line {line}: `#{directive}` inside the arguments of `{name}`, opened on line {open_line}, has undefined behavior")]
+    DirectiveInMacroArguments {
+        directive: BString,
+        name: BString,
+        open_line: u32,
+        line: u32,
+    },
+    #[error("line {line}: `{name}` is defined as a macro, which #if expands instead of taking the keyword")]
+    KeywordMacro { name: BString, line: u32 },
+    #[error("line {line}: `{name}` expands to `defined`, which has undefined behavior in #if")]
+    ExpansionToDefined { name: BString, line: u32 },
+}
+
+fn at_column(column: &Option<u32>) -> String {
+    column.map_or_else(String::new, |column| format!(", column {column}"))
+}
+
+// which warnings are reported, and which of those as errors.
+#[derive(Debug, Clone)]
+pub struct WarningOptions {
+    enabled: HashSet<WarningFlag>,
+    errors: HashSet<WarningFlag>,
+}
+
+impl WarningFlag {
+    pub const ALL: &'static [Self] = &[
+        Self::UnknownPragmas,
+        Self::PragmaMessages,
+        Self::Normalized,
+        Self::ImplementationLimits,
+        Self::Pedantic,
+        Self::InvalidEscapes,
+        Self::EmbeddedDirective,
+        Self::KeywordMacro,
+        Self::ExpansionToDefined,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UnknownPragmas => "unknown-pragmas",
+            Self::PragmaMessages => "#pragma-messages",
+            Self::Normalized => "normalized",
+            Self::ImplementationLimits => "implementation-limits",
+            Self::Pedantic => "pedantic",
+            Self::InvalidEscapes => "invalid-escapes",
+            Self::EmbeddedDirective => "embedded-directive",
+            Self::KeywordMacro => "keyword-macro",
+            Self::ExpansionToDefined => "expansion-to-defined",
+        }
+    }
+
+    pub fn enabled_by_default(self) -> bool {
+        match self {
+            Self::UnknownPragmas => false,
+            Self::PragmaMessages => true,
+            Self::Normalized => true,
+            Self::ImplementationLimits => false,
+            Self::Pedantic => false,
+            Self::InvalidEscapes => true,
+            Self::EmbeddedDirective => true,
+            Self::KeywordMacro => true,
+            Self::ExpansionToDefined => false,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unknown-pragmas" => Some(Self::UnknownPragmas),
+            "#pragma-messages" => Some(Self::PragmaMessages),
+            "normalized" => Some(Self::Normalized),
+            "implementation-limits" => Some(Self::ImplementationLimits),
+            "pedantic" => Some(Self::Pedantic),
+            "invalid-escapes" => Some(Self::InvalidEscapes),
+            "embedded-directive" => Some(Self::EmbeddedDirective),
+            "keyword-macro" => Some(Self::KeywordMacro),
+            "expansion-to-defined" => Some(Self::ExpansionToDefined),
+            _ => None,
+        }
+    }
+}
+
+impl Warning {
+    // stable across releases; a retired code is never reused.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownPragma { .. } => "PP1001",
+            Self::PragmaMessage { .. } => "PP1002",
+            Self::NotNfc { .. } => "PP1003",
+            Self::LimitExceeded { .. } => "PP1004",
+            Self::Extension { .. } => "PP1005",
+            Self::InvalidEscape { .. } => "PP1006",
+            Self::DirectiveInMacroArguments { .. } => "PP1007",
+            Self::KeywordMacro { .. } => "PP1008",
+            Self::ExpansionToDefined { .. } => "PP1009",
+        }
+    }
+
+    pub fn line(&self) -> u32 {
+        match self {
+            Self::UnknownPragma { line, .. }
+            | Self::PragmaMessage { line, .. }
+            | Self::NotNfc { line, .. }
+            | Self::LimitExceeded { line, .. }
+            | Self::Extension { line, .. }
+            | Self::InvalidEscape { line, .. }
+            | Self::DirectiveInMacroArguments { line, .. }
+            | Self::KeywordMacro { line, .. }
+            | Self::ExpansionToDefined { line, .. } => *line,
+        }
+    }
+
+    pub fn flag(&self) -> WarningFlag {
+        match self {
+            Self::UnknownPragma { .. } => WarningFlag::UnknownPragmas,
+            Self::PragmaMessage { .. } => WarningFlag::PragmaMessages,
+            Self::NotNfc { .. } => WarningFlag::Normalized,
+            Self::LimitExceeded { .. } => WarningFlag::ImplementationLimits,
+            Self::Extension { .. } => WarningFlag::Pedantic,
+            Self::InvalidEscape { .. } => WarningFlag::InvalidEscapes,
+            Self::DirectiveInMacroArguments { .. } => WarningFlag::EmbeddedDirective,
+            Self::KeywordMacro { .. } => WarningFlag::KeywordMacro,
+            Self::ExpansionToDefined { .. } => WarningFlag::ExpansionToDefined,
+        }
+    }
+}
+
+impl Default for WarningOptions {
+    fn default() -> Self {
+        let enabled = WarningFlag::ALL
+            .iter()
+            .copied()
+            .filter(|flag| flag.enabled_by_default())
+            .collect();
+        Self {
+            enabled,
+            errors: HashSet::new(),
+        }
+    }
+}
+
+impl WarningOptions {
+    pub fn enable(&mut self, flag: WarningFlag) {
+        self.enabled.insert(flag);
+        self.errors.remove(&flag);
+    }
+
+    pub fn disable(&mut self, flag: WarningFlag) {
+        self.enabled.remove(&flag);
+        self.errors.remove(&flag);
+    }
+
+    // report the warning as an error.
+    pub fn promote(&mut self, flag: WarningFlag) {
+        self.enabled.insert(flag);
+        self.errors.insert(flag);
+    }
+
+    pub fn is_error(&self, flag: WarningFlag) -> bool {
+        self.errors.contains(&flag)
+    }
+
+    pub fn is_enabled(&self, flag: WarningFlag) -> bool {
+        self.enabled.contains(&flag)
+    }
+
+    // how `warning` is reported, if it is.
+    pub fn diagnose(&self, warning: &Warning) -> Option<Diagnostic> {
+        let flag = warning.flag();
+        if !self.is_enabled(flag) {
+            None
+        } else if self.is_error(flag) {
+            Some(Diagnostic::promoted(warning))
+        } else {
+            Some(Diagnostic::from(warning))
+        }
+    }
+}
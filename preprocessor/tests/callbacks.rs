@@ -0,0 +1,121 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use bstr::BStr;
+
+use preprocessor::callbacks::Callbacks;
+use preprocessor::lexer::lex_with_options;
+use preprocessor::options::CxxStandard;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::ParseError;
+use preprocessor::parser::Parser;
+use preprocessor::pragma::Pragma;
+use preprocessor::token::Token;
+
+// what the parser told the callbacks, one line per call.
+#[derive(Clone, Default)]
+struct Recorder(Rc<RefCell<Vec<String>>>);
+
+impl Callbacks for Recorder {
+    fn ident(&mut self, text: &BStr) {
+        self.0.borrow_mut().push(format!("ident {text}"));
+    }
+
+    fn module_directive(&mut self, tokens: &[Token<'_>]) {
+        self.0
+            .borrow_mut()
+            .push(format!("module {}", spell(tokens)));
+    }
+
+    fn pragma(&mut self, pragma: &Pragma<'_>) -> bool {
+        let (kind, tokens) = match pragma {
+            Pragma::Region(tokens) => ("region", tokens),
+            Pragma::EndRegion(tokens) => ("endregion", tokens),
+            _ => return false,
+        };
+        self.0
+            .borrow_mut()
+            .push(format!("{kind} {}", spell(tokens)));
+        true
+    }
+}
+
+fn spell(tokens: &[Token<'_>]) -> String {
+    let spellings: Vec<_> = tokens
+        .iter()
+        .map(|tok| tok.spelling().to_string())
+        .collect();
+    spellings.join(" ")
+}
+
+// the calls made while preprocessing `src`, or the first error.
+fn calls(src: &str, options: LangOptions) -> Result<Vec<String>, ParseError> {
+    let recorder = Recorder::default();
+    let parser = Parser::new(lex_with_options(BStr::new(src), options.clone()), options)
+        .with_callbacks(Box::new(recorder.clone()));
+    parser.collect::<Result<Vec<_>, _>>()?;
+    Ok(recorder.0.take())
+}
+
+#[test]
+fn ident_and_sccs() {
+    let src = "#ident \"a 1.0\"\n#sccs \"@(#)b\"\n#if 0\n#ident \"c\"\n#endif\n";
+    assert_eq!(
+        calls(src, LangOptions::default()).unwrap(),
+        ["ident \"a 1.0\"", "ident \"@(#)b\""]
+    );
+    assert!(matches!(
+        calls("#ident a\n", LangOptions::default()),
+        Err(ParseError::InvalidIdent)
+    ));
+    assert!(matches!(
+        calls("#ident <a>\n", LangOptions::default()),
+        Err(ParseError::InvalidIdent)
+    ));
+}
+
+#[test]
+fn module_directives() {
+    let src = "\
+module;
+export module m.n;
+import <vector>;
+import(x);
+module::f();
+";
+    let cxx = |std| LangOptions {
+        cplusplus: Some(std),
+        ..LangOptions::default()
+    };
+    assert_eq!(
+        calls(src, cxx(CxxStandard::Cxx20)).unwrap(),
+        [
+            "module module ;",
+            "module export module m . n ;",
+            "module import <vector> ;",
+        ]
+    );
+    assert!(calls(src, cxx(CxxStandard::Cxx17)).unwrap().is_empty());
+
+    let cxx20 = cxx(CxxStandard::Cxx20);
+    let tokens: Vec<_> = Parser::new(lex_with_options(BStr::new(src), cxx20.clone()), cxx20)
+        .map(Result::unwrap)
+        .collect();
+    let keywords: Vec<_> = tokens
+        .iter()
+        .filter_map(|tok| match tok {
+            Token::ModuleKeyword(keyword) => Some(keyword.to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(keywords, ["module", "export", "module", "import"]);
+}
+
+#[test]
+fn region_pragmas() {
+    let src = "#pragma region Helpers\n#pragma endregion end of helpers\n#pragma once\n";
+    assert_eq!(
+        calls(src, LangOptions::default()).unwrap(),
+        ["region Helpers", "endregion end of helpers"]
+    );
+}
@@ -0,0 +1,119 @@
+use bstr::BStr;
+
+use preprocessor::lexer::lex;
+use preprocessor::options::CStandard;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::ParseError;
+use preprocessor::parser::Parser;
+use preprocessor::token::Token;
+
+// the tokens left after preprocessing `src`, or the first error.
+fn preprocess(src: &str, options: LangOptions) -> Result<Vec<Token<'_>>, ParseError> {
+    Parser::new(lex(BStr::new(src)), options).collect()
+}
+
+// whether the group under `#if condition`, after `prelude`, is entered.
+fn group_entered(prelude: &str, condition: &str, options: LangOptions) -> bool {
+    let src = format!("{prelude}#if {condition}\nyes\n#endif\n");
+    let tokens = preprocess(&src, options).unwrap();
+    tokens.contains(&Token::Ident(BStr::new("yes")))
+}
+
+fn entered(condition: &str) -> bool {
+    group_entered("", condition, LangOptions::default())
+}
+
+fn error(condition: &str) -> ParseError {
+    let src = format!("#if {condition}\n#endif\n");
+    preprocess(&src, LangOptions::default()).unwrap_err()
+}
+
+#[test]
+fn character_constants() {
+    assert!(entered("'a' == 97"));
+    assert!(entered(r"'\377' < 0"));
+    assert!(entered("'ab' == 0x6162"));
+    assert!(entered(r"u'\xffff' == 65535"));
+}
+
+#[test]
+fn true_and_false() {
+    assert!(entered("true && !false"));
+    assert!(entered("true + true == 2"));
+    // before C23 they are identifiers like any other, so 0
+    let c17 = LangOptions {
+        c_standard: CStandard::C17,
+        ..LangOptions::default()
+    };
+    assert!(!group_entered("", "true", c17));
+}
+
+#[test]
+fn unevaluated_operands_are_not_checked() {
+    assert!(!entered("0 && 1 / 0"));
+    assert!(entered("1 || 1 / 0"));
+    assert!(entered("1 ? 2 : 1 / 0"));
+    assert!(entered("0 ? 1 % 0 : 3"));
+    assert!(matches!(
+        error("1 / 0"),
+        ParseError::DivisionByZeroInCondition
+    ));
+    assert!(matches!(
+        error("1 && 1 % 0"),
+        ParseError::DivisionByZeroInCondition
+    ));
+}
+
+#[test]
+fn signed_and_unsigned_arithmetic() {
+    assert!(entered("-1 > 0u"));
+    assert!(entered("-1 / 2 == 0"));
+    assert!(entered("-1 >> 1 == -1"));
+    assert!(entered("-1 % 2u == 1"));
+    assert!(entered("(0u - 1) / 2 == 0x7fffffffffffffff"));
+    assert!(entered("0xffffffffffffffff == -1"));
+    assert!(entered("(1u << 63) > 0"));
+    assert!(matches!(
+        error("18446744073709551616"),
+        ParseError::InvalidIntegerInCondition(..)
+    ));
+}
+
+#[test]
+fn object_like_macros_are_expanded() {
+    let defines = "#define N 3\n#define TWICE N * 2\n#define EMPTY\n";
+    let options = LangOptions::default;
+    assert!(group_entered(defines, "TWICE == 6 EMPTY", options()));
+    assert!(group_entered(
+        defines,
+        "defined TWICE && defined(EMPTY)",
+        options()
+    ));
+    // a macro isn't expanded inside its own replacement
+    assert!(group_entered("#define A A + 1\n", "A == 1", options()));
+    // nor when it is the operand of `defined`
+    assert!(group_entered(
+        "#define D UNDEFINED\n",
+        "defined D",
+        options()
+    ));
+}
+
+#[test]
+fn assertions() {
+    let gnu = || LangOptions {
+        gnu_extensions: true,
+        ..LangOptions::default()
+    };
+    let prelude = "#assert machine(x86 64)\n#assert machine(arm)\n#assert system(unix)\n";
+    assert!(group_entered(prelude, "#machine(x86 64)", gnu()));
+    assert!(group_entered(prelude, "#machine && !#machine(mips)", gnu()));
+    let prelude = format!("{prelude}#unassert machine(arm)\n#unassert system\n");
+    assert!(group_entered(&prelude, "#machine(x86 64)", gnu()));
+    assert!(!group_entered(&prelude, "#machine(arm) || #system", gnu()));
+    // the directives are GNU extensions
+    assert!(matches!(
+        preprocess("#assert machine(x86)\n", LangOptions::default()),
+        Err(ParseError::InvalidDirective(_))
+    ));
+}
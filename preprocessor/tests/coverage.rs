@@ -0,0 +1,68 @@
+use bstr::BStr;
+
+use preprocessor::coverage::GroupState;
+use preprocessor::lexer::lex;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::Parser;
+
+fn states(src: &str) -> Vec<(u32, GroupState)> {
+    let mut parser = Parser::new(lex(BStr::new(src)), LangOptions::default());
+    parser.by_ref().for_each(drop);
+    parser
+        .coverage()
+        .groups
+        .iter()
+        .map(|group| (group.line, group.state))
+        .collect()
+}
+
+#[test]
+fn evaluated_groups() {
+    let src = "#if 0\n#elif 1\n#elif 1\n#else\n#endif\n#if 0\n#if 1\n#endif\n#endif\n";
+    assert_eq!(
+        states(src),
+        [
+            (1, GroupState::Skipped),
+            (2, GroupState::Entered),
+            (3, GroupState::NotEvaluated),
+            (4, GroupState::NotEvaluated),
+            (6, GroupState::Skipped),
+            (7, GroupState::NotEvaluated),
+        ]
+    );
+}
+
+#[test]
+fn function_like_macros_make_groups_unknown() {
+    let src = "\
+#define F(x) x
+#define G F
+#if F(1)
+#if 1
+#endif
+#elif 1
+#else
+#endif
+#if 0
+#elif G(1)
+#else
+#endif
+#if 1
+#elif F(1)
+#endif
+";
+    assert_eq!(
+        states(src),
+        [
+            (3, GroupState::Unknown),
+            (4, GroupState::NotEvaluated),
+            (6, GroupState::Unknown),
+            (7, GroupState::Unknown),
+            (9, GroupState::Skipped),
+            (10, GroupState::Unknown),
+            (11, GroupState::Unknown),
+            (13, GroupState::Entered),
+            (14, GroupState::NotEvaluated),
+        ]
+    );
+}
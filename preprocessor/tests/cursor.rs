@@ -0,0 +1,75 @@
+use bstr::BStr;
+
+use preprocessor::cursor::TokenCursor;
+use preprocessor::lexer::lex;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::ParseError;
+use preprocessor::parser::Parser;
+use preprocessor::token::Token;
+
+fn ident(name: &str) -> Token<'_> {
+    Token::Ident(BStr::new(name))
+}
+
+#[test]
+fn peek_does_not_consume() {
+    let mut cursor = TokenCursor::new(lex(BStr::new("a b c")));
+    assert_eq!(cursor.peek(), Some(ident("a")));
+    assert_eq!(cursor.peek_nth(2), Some(ident("c")));
+    assert_eq!(cursor.next(), Some(ident("a")));
+    assert_eq!(cursor.peek(), Some(ident("b")));
+}
+
+#[test]
+fn peek_is_limited_to_the_lookahead() {
+    let mut cursor = TokenCursor::new(lex(BStr::new("a b c"))).with_lookahead(1);
+    assert_eq!(cursor.peek_nth(1), Some(ident("b")));
+    assert_eq!(cursor.peek_nth(2), None);
+    // past the end of the stream
+    let mut cursor = TokenCursor::new(lex(BStr::new("a")));
+    assert_eq!(cursor.peek_nth(1), Some(Token::Eof));
+    assert_eq!(cursor.peek_nth(2), None);
+}
+
+#[test]
+fn rewind_returns_the_tokens_again() {
+    let mut cursor = TokenCursor::new(lex(BStr::new("a b c d")));
+    cursor.next();
+    let outer = cursor.checkpoint();
+    cursor.next();
+    let inner = cursor.checkpoint();
+    cursor.next();
+    cursor.rewind(inner);
+    assert_eq!(cursor.next(), Some(ident("c")));
+    cursor.rewind(outer);
+    assert_eq!(cursor.next(), Some(ident("b")));
+    assert_eq!(cursor.next(), Some(ident("c")));
+}
+
+#[test]
+fn commit_keeps_the_tokens_read() {
+    let mut cursor = TokenCursor::new(lex(BStr::new("a b c")));
+    let checkpoint = cursor.checkpoint();
+    cursor.next();
+    cursor.next();
+    cursor.commit(checkpoint);
+    assert_eq!(cursor.collect::<Vec<_>>(), [ident("c"), Token::Eof]);
+}
+
+#[test]
+fn over_parser_results() {
+    let src = BStr::new("#if 1 / 0\n#endif\na\n");
+    let parser = Parser::new(lex(src), LangOptions::default());
+    let mut cursor = TokenCursor::new(parser);
+    let checkpoint = cursor.checkpoint();
+    let error = |next| matches!(next, Some(Err(ParseError::DivisionByZeroInCondition)));
+    assert!(error(cursor.next()));
+    cursor.rewind(checkpoint);
+    // the error comes back from the buffer rather than being reported twice
+    assert!(error(cursor.next()));
+    let rest: Vec<_> = cursor.map(Result::unwrap).collect();
+    assert_eq!(
+        rest,
+        [Token::Eol, Token::Eol, ident("a"), Token::Eol, Token::Eof]
+    );
+}
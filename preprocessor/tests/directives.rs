@@ -0,0 +1,118 @@
+use bstr::BStr;
+use bstr::BString;
+
+use preprocessor::lexer::lex;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::ParseError;
+use preprocessor::parser::Parser;
+use preprocessor::token::Token;
+
+// the presumed file and line of the `#if` that ends `src`.
+fn location_of_if(src: &str) -> Result<(Option<BString>, u32), ParseError> {
+    let src = format!("{src}#if 1\n#endif\n");
+    let mut parser = Parser::new(lex(BStr::new(&src)), LangOptions::default());
+    if let Some(e) = parser.by_ref().find_map(Result::err) {
+        return Err(e);
+    }
+    let group = &parser.coverage().groups[0];
+    Ok((group.file.clone(), group.line))
+}
+
+#[test]
+fn line_directives() {
+    assert_eq!(location_of_if("#line 10\n").unwrap(), (None, 10));
+    assert_eq!(
+        location_of_if("#line 10 \"a.c\"\n\n").unwrap(),
+        (Some("a.c".into()), 11)
+    );
+    // the operands are macro-expanded
+    let src = "#define LINE 20\n#define FILE \"b.c\"\n#line LINE FILE\n";
+    assert_eq!(location_of_if(src).unwrap(), (Some("b.c".into()), 20));
+    assert!(matches!(
+        location_of_if("#line X\n"),
+        Err(ParseError::InvalidLineNumber(_))
+    ));
+    assert!(matches!(
+        location_of_if("#define F(x) x\n#line F(1)\n"),
+        Err(ParseError::InvalidLineNumber(_))
+    ));
+}
+
+// the error from defining `definition`, and whether `NAME` was defined.
+fn define(definition: &str) -> (Option<ParseError>, bool) {
+    let src = format!("#define {definition}\n#ifdef NAME\nyes\n#endif\n");
+    let src = BStr::new(&src);
+    let mut parser = Parser::new(lex(src), LangOptions::default()).with_text(src);
+    let mut error = None;
+    let mut defined = false;
+    for result in parser.by_ref() {
+        match result {
+            Ok(tok) => defined |= tok.spelling() == "yes",
+            Err(e) => error = error.or(Some(e)),
+        }
+    }
+    (error, defined)
+}
+
+#[test]
+fn pastes_of_fixed_tokens_are_checked() {
+    assert!(matches!(define("NAME a ## 1 ## b"), (None, true)));
+    assert!(matches!(define("NAME - ## -"), (None, true)));
+    // pasted left to right: `--` then `---`
+    assert!(matches!(
+        define("NAME - ## - ## -"),
+        (Some(ParseError::InvalidPaste(..)), false)
+    ));
+    assert!(matches!(define("NAME(x) x ## - ## -"), (None, true)));
+    // a spaced `(` starts the replacement list, so nothing here is a parameter
+    assert!(matches!(define("NAME (x) a ## x"), (None, true)));
+    assert!(matches!(
+        define("NAME (x) ( ## x"),
+        (Some(ParseError::InvalidPaste(..)), false)
+    ));
+    assert!(matches!(define("NAME(x) ( ## x"), (None, true)));
+}
+
+fn preprocess(src: &str) -> Result<Vec<Token<'_>>, ParseError> {
+    Parser::new(lex(BStr::new(src)), LangOptions::default()).collect()
+}
+
+#[test]
+fn null_directives_and_non_directives() {
+    assert_eq!(
+        preprocess("#\na\n#\n").unwrap(),
+        [
+            Token::Eol,
+            Token::Ident(BStr::new("a")),
+            Token::Eol,
+            Token::Eol,
+            Token::Eof
+        ]
+    );
+    // a number after `#` is a linemarker rather than an invalid directive
+    assert!(preprocess("# 1 \"a.c\"\n").is_ok());
+    assert!(matches!(
+        preprocess("#!\n"),
+        Err(ParseError::InvalidDirective(_))
+    ));
+    // nothing in a skipped group is checked
+    assert!(preprocess("#if 0\n#!\n# x\n#endif\n").is_ok());
+}
+
+#[test]
+fn linemarkers() {
+    assert_eq!(
+        location_of_if("# 5 \"a.h\" 1 3\n").unwrap(),
+        (Some("a.h".into()), 5)
+    );
+    assert_eq!(
+        location_of_if("# 7 \"b.h\"\n# 2\n\n").unwrap(),
+        (Some("b.h".into()), 3)
+    );
+    // the marker GCC starts its output with
+    assert_eq!(location_of_if("# 0 \"<built-in>\"\n").unwrap().1, 0);
+    assert!(matches!(
+        location_of_if("# 1 \"a.h\" 5\n"),
+        Err(ParseError::InvalidLinemarkerFlag(_))
+    ));
+}
@@ -0,0 +1,21 @@
+use std::env;
+use std::path::Path;
+
+use preprocessor::golden;
+use preprocessor::golden::Config;
+
+// set BLESS to rewrite the expectations from the current output.
+#[test]
+fn golden_files() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let bless = env::var_os("BLESS").is_some();
+    let mismatches = golden::check_dir(&dir, &Config::default(), bless).unwrap();
+    for mismatch in &mismatches {
+        eprint!("{mismatch}");
+    }
+    assert!(
+        mismatches.is_empty(),
+        "{} golden files differ",
+        mismatches.len()
+    );
+}
@@ -0,0 +1,5 @@
+#error stop here
+#undef
+#define 3
+#endif
+after
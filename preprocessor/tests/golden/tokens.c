@@ -0,0 +1,5 @@
+#define GREETING "hello"
+int main(void) {
+    return x->y <<= 0x1p-3 + 'c';
+}
+#undef GREETING
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use bstr::BStr;
+use bstr::BString;
+
+use preprocessor::files::FileProvider;
+use preprocessor::lexer::lex;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::ParseError;
+use preprocessor::parser::Parser;
+use preprocessor::token::Token;
+
+// headers by name, found wherever they are included from.
+struct Headers(HashMap<&'static str, &'static str>);
+
+impl FileProvider for Headers {
+    fn resolve(&self, name: &BStr, _quoted: bool, _from: &BStr) -> Option<BString> {
+        let path = format!("/inc/{name}");
+        self.0.contains_key(&path[5..]).then(|| path.into())
+    }
+
+    fn read(&self, path: &BStr) -> Option<Vec<u8>> {
+        let name = path.strip_prefix(b"/inc/")?;
+        let name = std::str::from_utf8(name).ok()?;
+        self.0.get(name).map(|text| text.as_bytes().to_vec())
+    }
+}
+
+fn parser(src: &str) -> Parser<'_, impl Iterator<Item = Token<'_>>> {
+    let headers = Headers(HashMap::from([
+        ("a.h", "#include \"b.h\"\n#include <missing.h>\n"),
+        ("b.h", "\n#include \"a.h\"\n"),
+    ]));
+    let mut parser = Parser::new(lex(BStr::new(src)), LangOptions::default())
+        .with_file_name("main.c")
+        .with_file_provider(Box::new(headers));
+    parser.by_ref().for_each(drop);
+    parser
+}
+
+#[test]
+fn headers_are_followed() {
+    let parser = parser("#include \"a.h\"\n#include \"b.h\"\n");
+    let direct = parser.include_graph();
+    assert_eq!(direct.files(), ["main.c", "/inc/a.h", "/inc/b.h"]);
+    assert_eq!(direct.edges.len(), 2);
+
+    let graph = parser.full_include_graph();
+    let edges: Vec<_> = graph
+        .edges
+        .iter()
+        .map(|edge| {
+            let files = graph.files();
+            (
+                files[edge.from].to_string(),
+                files[edge.to].to_string(),
+                edge.line,
+                edge.resolved,
+            )
+        })
+        .collect();
+    let edge =
+        |from: &str, to: &str, line, resolved| (from.to_owned(), to.to_owned(), line, resolved);
+    assert_eq!(
+        edges,
+        [
+            edge("main.c", "/inc/a.h", 1, true),
+            edge("main.c", "/inc/b.h", 2, true),
+            edge("/inc/a.h", "/inc/b.h", 1, true),
+            edge("/inc/a.h", "missing.h", 2, false),
+            // each header is followed once, so the cycle ends here
+            edge("/inc/b.h", "/inc/a.h", 2, true),
+        ]
+    );
+}
+
+#[test]
+fn dot_labels_are_escaped() {
+    let parser = parser("#include \"a\\\"b.h\"\n#include <c\\d.h>\n");
+    let dot = parser.include_graph().to_dot();
+    assert!(dot.contains(r#"n1 [label="a\\\"b.h"];"#), "{dot}");
+    assert!(dot.contains(r#"n2 [label="c\\d.h"];"#), "{dot}");
+}
+
+#[test]
+fn computed_includes() {
+    let src = "\
+#define QUOTED \"a.h\"
+#define ANGLED <sys/b.h>
+#define HEADER QUOTED
+#include HEADER
+#include ANGLED
+";
+    let parser = parser(src);
+    let spellings: Vec<_> = parser
+        .include_graph()
+        .edges
+        .iter()
+        .map(|edge| edge.spelling.to_string())
+        .collect();
+    assert_eq!(spellings, ["\"a.h\"", "<sys/b.h>"]);
+    assert_eq!(
+        parser.include_graph().files(),
+        ["main.c", "/inc/a.h", "sys/b.h"]
+    );
+
+    // function-like macros aren't expanded here yet
+    let error =
+        |src| Parser::new(lex(BStr::new(src)), LangOptions::default()).find_map(Result::err);
+    assert!(matches!(
+        error("#define H(x) #x\n#include H(a.h)\n"),
+        Some(ParseError::InvalidInclude)
+    ));
+    assert!(matches!(
+        error("#define H a.h\n#include H\n"),
+        Some(ParseError::InvalidInclude)
+    ));
+}
@@ -0,0 +1,60 @@
+use bstr::BStr;
+
+use preprocessor::incremental::Document;
+use preprocessor::incremental::EditError;
+use preprocessor::options::CxxStandard;
+use preprocessor::options::LangOptions;
+use preprocessor::token::Token;
+
+fn cxx() -> LangOptions {
+    LangOptions {
+        cplusplus: Some(CxxStandard::Cxx20),
+        ..LangOptions::default()
+    }
+}
+
+fn assert_same(doc: &Document) {
+    let fresh = Document::new(doc.source(), cxx());
+    assert_eq!(doc.text(), fresh.text());
+    assert_eq!(
+        doc.tokens().collect::<Vec<_>>(),
+        fresh.tokens().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn edits_match_a_fresh_document() {
+    let src = "int a;\nchar c = '\"';\n/* x\ny */ int b;\nauto s = R\"(1\"\n2)\";\n";
+    let mut doc = Document::new(BStr::new(src), cxx());
+    let edits = [
+        (0..3, "long"),
+        (7..7, "/*"),
+        (0..0, "#define A 1\n"),
+        (0..0, "R\"(\n"),
+        (0..4, ""),
+    ];
+    for (range, replacement) in edits {
+        doc.edit(range, replacement.as_bytes()).unwrap();
+        assert_same(&doc);
+    }
+    assert!(doc
+        .tokens()
+        .any(|tok| tok == Token::StringLit(BStr::new("R\"(1\"\n2)\""))));
+}
+
+#[test]
+fn edits_outside_the_source_are_errors() {
+    let mut doc = Document::new(BStr::new("a\n"), cxx());
+    assert_eq!(
+        doc.edit(1..3, b"b"),
+        Err(EditError::InvalidRange {
+            start: 1,
+            end: 3,
+            len: 2
+        })
+    );
+    #[allow(clippy::reversed_empty_ranges)]
+    let backwards = 2..1;
+    assert!(doc.edit(backwards, b"").is_err());
+    assert_eq!(doc.source(), "a\n");
+}
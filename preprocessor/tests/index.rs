@@ -0,0 +1,50 @@
+use bstr::BStr;
+
+use preprocessor::lexer::lex;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::Parser;
+
+// the lines each macro is used on.
+fn uses(src: &str) -> Vec<(String, Vec<u32>)> {
+    let mut parser = Parser::new(lex(BStr::new(src)), LangOptions::default());
+    parser.by_ref().for_each(drop);
+    let mut uses: Vec<_> = parser
+        .macro_index()
+        .iter()
+        .map(|(name, entry)| {
+            let lines = entry.uses.iter().map(|site| site.line).collect();
+            (name.to_string(), lines)
+        })
+        .collect();
+    uses.sort();
+    uses
+}
+
+#[test]
+fn uses_in_conditions() {
+    let src = "\
+#define A B
+#define B 1
+#define F(x) x
+#if A
+#endif
+#ifndef A
+#elif defined(B) || defined C
+#endif
+#ifdef F
+#endif
+#if F(1)
+#endif
+A
+";
+
+    assert_eq!(
+        uses(src),
+        [
+            ("A".into(), vec![4, 6, 13]),
+            ("B".into(), vec![4, 7]),
+            ("C".into(), vec![7]),
+            ("F".into(), vec![9, 11]),
+        ]
+    );
+}
@@ -0,0 +1,56 @@
+use bstr::BStr;
+
+use preprocessor::keyword::Keywords;
+use preprocessor::lexer::lex;
+use preprocessor::normalize::Normalized;
+use preprocessor::options::CStandard;
+use preprocessor::options::CxxStandard;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::Parser;
+use preprocessor::token::Token;
+
+// the spellings phase 7 takes as keywords in `src`.
+fn keywords(src: &str, options: LangOptions) -> Vec<String> {
+    let parser = Parser::new(lex(BStr::new(src)), options.clone());
+    Keywords::new(Normalized::new(parser), &options)
+        .map(Result::unwrap)
+        .filter_map(|tok| match tok {
+            Token::Keyword(name) => Some(name.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn c(c_standard: CStandard) -> LangOptions {
+    LangOptions {
+        c_standard,
+        ..LangOptions::default()
+    }
+}
+
+#[test]
+fn keywords_depend_on_the_standard() {
+    let src = "int _Bool bool true nullptr typeof _Static_assert class\n";
+    assert_eq!(keywords(src, c(CStandard::C89)), ["int"]);
+    assert_eq!(keywords(src, c(CStandard::C99)), ["int", "_Bool"]);
+    assert_eq!(
+        keywords(src, c(CStandard::C23)),
+        [
+            "int",
+            "_Bool",
+            "bool",
+            "true",
+            "nullptr",
+            "typeof",
+            "_Static_assert"
+        ]
+    );
+    let cxx = LangOptions {
+        cplusplus: Some(CxxStandard::Cxx11),
+        ..LangOptions::default()
+    };
+    assert_eq!(
+        keywords(src, cxx),
+        ["int", "bool", "true", "nullptr", "class"]
+    );
+}
@@ -0,0 +1,161 @@
+use bstr::BStr;
+
+use preprocessor::lexer::lex_with_options;
+use preprocessor::options::CStandard;
+use preprocessor::options::CxxStandard;
+use preprocessor::options::LangOptions;
+use preprocessor::token::Punct;
+use preprocessor::token::Token;
+
+fn c() -> LangOptions {
+    LangOptions::default()
+}
+
+fn cxx(std: CxxStandard) -> LangOptions {
+    LangOptions {
+        cplusplus: Some(std),
+        ..LangOptions::default()
+    }
+}
+
+fn tokens(src: &str, options: LangOptions) -> Vec<Token<'_>> {
+    lex_with_options(BStr::new(src), options)
+        .take_while(|tok| *tok != Token::Eof)
+        .collect()
+}
+
+fn punct(p: Punct) -> Token<'static> {
+    Token::Punct(p)
+}
+
+#[test]
+fn header_names_only_where_a_header_is_expected() {
+    let header = Token::StringLit(BStr::new("<a b.h>"));
+    assert_eq!(tokens("#include <a b.h>", c())[2], header);
+    assert_eq!(tokens("# embed <a b.h>", c())[2], header);
+    assert_eq!(tokens("#if __has_include(<a b.h>)", c())[4], header);
+    assert_eq!(tokens("#if __has_include (<a b.h>)", c())[4], header);
+
+    let elsewhere = [
+        "a <a b.h>",
+        "#define X <a b.h>",
+        "#if 1 < 2 > 0",
+        "#if __has_include && a <a b.h>",
+    ];
+    for src in elsewhere {
+        let tokens = tokens(src, c());
+        assert!(!tokens.contains(&header), "{src}");
+        assert!(tokens.contains(&punct(Punct::Lt)), "{src}");
+    }
+    // the next line starts afresh
+    assert_eq!(
+        tokens("#include\n<a>", c())[3..],
+        [
+            punct(Punct::Lt),
+            Token::Ident(BStr::new("a")),
+            punct(Punct::Gt)
+        ]
+    );
+}
+
+#[test]
+fn literals_starting_with_digraph_characters() {
+    assert_eq!(
+        tokens(r#"":" '%' "%>""#, c()),
+        [
+            Token::StringLit(BStr::new(r#"":""#)),
+            Token::StringLit(BStr::new("'%'")),
+            Token::StringLit(BStr::new(r#""%>""#)),
+        ]
+    );
+}
+
+#[test]
+fn digraphs() {
+    let expected = [
+        punct(Punct::Hash),
+        punct(Punct::HashHash),
+        punct(Punct::LBrack),
+        punct(Punct::RBrack),
+        punct(Punct::LBrace),
+        punct(Punct::RBrace),
+    ];
+    assert_eq!(tokens("%: %:%: <: :> <% %>", c()), expected);
+
+    let options = LangOptions {
+        digraphs: false,
+        ..c()
+    };
+    assert_eq!(
+        tokens("<: %:", options),
+        [
+            punct(Punct::Lt),
+            punct(Punct::Colon),
+            punct(Punct::Percent),
+            punct(Punct::Colon),
+        ]
+    );
+}
+
+#[test]
+fn cxx_punctuators() {
+    let cxx20 = cxx(CxxStandard::Cxx20);
+    assert_eq!(
+        tokens(":: .* ->* <=>", cxx20.clone()),
+        [
+            punct(Punct::ColonColon),
+            punct(Punct::PeriodStar),
+            punct(Punct::ArrowStar),
+            punct(Punct::Spaceship),
+        ]
+    );
+    assert_eq!(
+        tokens("<=>", cxx(CxxStandard::Cxx17)),
+        [punct(Punct::LtEq), punct(Punct::Gt)]
+    );
+    assert_eq!(
+        tokens("::", c()),
+        [punct(Punct::Colon), punct(Punct::Colon)]
+    );
+
+    // `<::` is `<` `::` unless the next character is `:` or `>`
+    assert_eq!(
+        tokens("a<::b", cxx20.clone())[1..3],
+        [punct(Punct::Lt), punct(Punct::ColonColon)]
+    );
+    assert_eq!(
+        tokens("a<::>", cxx20.clone())[1..],
+        [punct(Punct::LBrack), punct(Punct::RBrack)]
+    );
+    assert_eq!(
+        tokens("a<::b", c())[1..3],
+        [punct(Punct::LBrack), punct(Punct::Colon)]
+    );
+}
+
+#[test]
+fn cxx_alternative_tokens() {
+    assert_eq!(
+        tokens("a and b", cxx(CxxStandard::Cxx11))[1],
+        punct(Punct::AmpAmp)
+    );
+    assert_eq!(tokens("a and b", c())[1], Token::Ident(BStr::new("and")));
+}
+
+#[test]
+fn raw_strings() {
+    let src = r#"R"x(a)" b)x""#;
+    assert_eq!(
+        tokens(src, cxx(CxxStandard::Cxx11)),
+        [Token::StringLit(BStr::new(src))]
+    );
+    assert_eq!(
+        tokens(src, cxx(CxxStandard::Cxx98))[0],
+        Token::Ident(BStr::new("R"))
+    );
+    let c89 = LangOptions {
+        c_standard: CStandard::C89,
+        ..c()
+    };
+    assert_eq!(tokens(src, c89)[0], Token::Ident(BStr::new("R")));
+}
@@ -0,0 +1,74 @@
+use bstr::BStr;
+
+use preprocessor::lexer::lex;
+use preprocessor::limits::TranslationLimits;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::Parser;
+use preprocessor::warning::WarningFlag;
+use preprocessor::warning::WarningOptions;
+
+// the lines of the limits `src` exceeds, each with what was exceeded.
+fn exceeded(src: &str) -> Vec<String> {
+    let limits = TranslationLimits {
+        conditional_nesting: 1,
+        macro_parameters: 1,
+        macro_arguments: 1,
+        logical_line_length: 20,
+        // the predefined macros count as well
+        macro_definitions: TranslationLimits::for_options(&LangOptions::default())
+            .macro_definitions,
+    };
+    let mut warnings = WarningOptions::default();
+    warnings.enable(WarningFlag::ImplementationLimits);
+    let src = BStr::new(src);
+    let mut parser = Parser::new(lex(src), LangOptions::default())
+        .with_text(src)
+        .with_limits(limits)
+        .with_warning_options(warnings);
+    parser.by_ref().for_each(drop);
+    parser
+        .take_warnings()
+        .into_iter()
+        .map(|warning| warning.message)
+        .collect()
+}
+
+#[test]
+fn limits_in_live_code() {
+    let src = "\
+#if 1
+#if 1
+#endif
+#endif
+#define F(a, b) a
+#define G(a) a
+G(1) G((1, 2))
+G(1,
+  2)
+a_line_of_more_than_20
+";
+    let warnings = exceeded(src);
+    let expected = [
+        "line 2: conditional nesting depth is 2",
+        "line 5: number of macro parameters is 2",
+        "line 9: number of macro arguments is 2",
+        "line 10: logical line length is 22",
+    ];
+    assert_eq!(warnings.len(), expected.len(), "{warnings:?}");
+    for (warning, expected) in warnings.iter().zip(expected) {
+        assert!(warning.starts_with(expected), "{warning}");
+    }
+}
+
+#[test]
+fn skipped_groups_are_not_counted() {
+    let src = "\
+#if 0
+#if 1
+#endif
+#define F(a, b) a
+a_line_of_more_than_20
+#endif
+";
+    assert_eq!(exceeded(src), Vec::<String>::new());
+}
@@ -0,0 +1,96 @@
+use bstr::BStr;
+
+use preprocessor::literal;
+use preprocessor::literal::CharError;
+use preprocessor::literal::Encoding;
+use preprocessor::literal::EscapeError;
+use preprocessor::literal::Literal;
+use preprocessor::target::Arch;
+use preprocessor::target::Os;
+use preprocessor::target::Target;
+
+fn decode(spelling: &str) -> Option<Literal> {
+    literal::decode(BStr::new(spelling), &Target::default())
+}
+
+fn char_value(spelling: &str) -> Result<i64, CharError> {
+    decode(spelling).unwrap().char_value(&Target::default())
+}
+
+#[test]
+fn string_literals() {
+    let literal = decode(r#"u8"a\n\x41é""#).unwrap();
+    assert_eq!(literal.encoding, Encoding::Utf8);
+    assert_eq!(literal.delimiter, b'"');
+    assert_eq!(literal.units, [0x61, 0x0a, 0x41, 0xc3, 0xa9]);
+    assert!(literal.errors.is_empty());
+
+    let literal = decode(r#"u"\U0001F600""#).unwrap();
+    assert_eq!(literal.units, [0xd83d, 0xde00]);
+
+    let literal = decode(r#"L"é""#).unwrap();
+    assert_eq!(literal.encoding, Encoding::Wide);
+    assert_eq!(literal.units, [0xe9]);
+
+    assert_eq!(decode("abc"), None);
+    assert_eq!(decode("'a"), None);
+}
+
+#[test]
+fn escape_errors_point_into_the_spelling() {
+    let literal = decode(r#"L"a\qb\x""#).unwrap();
+    assert_eq!(
+        literal.errors,
+        [
+            (3..5, EscapeError::Unknown(r"\q".into())),
+            (6..8, EscapeError::MissingDigits(r"\x".into())),
+        ]
+    );
+
+    // an unknown escape stands for the character after the `\`
+    let literal = decode(r#""a\qb""#).unwrap();
+    assert_eq!(literal.units, [0x61, 0x71, 0x62]);
+
+    let literal = decode(r"'\x100'").unwrap();
+    assert_eq!(
+        literal.errors,
+        [(1..6, EscapeError::OutOfRange(r"\x100".into()))]
+    );
+}
+
+#[test]
+fn character_constants() {
+    assert_eq!(char_value("'a'"), Ok(97));
+    // `char` is signed on x86-64
+    assert_eq!(char_value(r"'\377'"), Ok(-1));
+    assert_eq!(char_value("'ab'"), Ok(0x6162));
+    assert_eq!(char_value("L'ab'"), Ok(98));
+    assert_eq!(char_value(r"U'\xffffffff'"), Ok(0xffff_ffff));
+    assert_eq!(char_value("u'ab'"), Err(CharError::TooLong));
+    assert_eq!(char_value("''"), Err(CharError::Empty));
+    assert_eq!(char_value(r#""a""#), Err(CharError::NotChar));
+
+    let arm = Target {
+        arch: Arch::Aarch64,
+        os: Some(Os::Linux),
+        hosted: true,
+    };
+    let literal = decode(r"'\377'").unwrap();
+    assert_eq!(literal.char_value(&arm), Ok(255));
+}
+
+#[test]
+fn wide_literals_take_the_width_of_wchar_t() {
+    let windows = Target {
+        arch: Arch::X86_64,
+        os: Some(Os::Windows),
+        hosted: true,
+    };
+    let literal = literal::decode(BStr::new(r#"L"\x110000😀""#), &windows).unwrap();
+    assert_eq!(literal.units, [0, 0xd83d, 0xde00]);
+    assert_eq!(
+        literal.errors,
+        [(2..10, EscapeError::OutOfRange(r"\x110000".into()))]
+    );
+    assert!(decode(r#"L"\x110000""#).unwrap().errors.is_empty());
+}
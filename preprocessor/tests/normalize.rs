@@ -0,0 +1,66 @@
+use bstr::BStr;
+
+use preprocessor::lexer::lex;
+use preprocessor::normalize::Normalized;
+use preprocessor::options::LangOptions;
+use preprocessor::options::OutputOptions;
+use preprocessor::parser::ParseError;
+use preprocessor::parser::Parser;
+use preprocessor::token::Token;
+
+fn ident(name: &str) -> Token<'_> {
+    Token::Ident(BStr::new(name))
+}
+
+#[test]
+fn line_ends_are_dropped_and_one_eof_ends_the_stream() {
+    let tokens = [Token::Eol, ident("a"), Token::Eol, Token::Eof, Token::Eof];
+    let normalized: Vec<_> = Normalized::new(tokens.into_iter().map(Ok::<_, ()>))
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(normalized, [ident("a"), Token::Eof]);
+
+    // a stream that stops without one still gets its `Eof`
+    let normalized: Vec<_> = Normalized::new([Ok::<_, ()>(ident("a"))].into_iter())
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(normalized, [ident("a"), Token::Eof]);
+}
+
+#[test]
+fn errors_are_passed_along() {
+    let src = BStr::new("a\n#if 1 / 0\n#endif\nb\n");
+    let results: Vec<_> = Normalized::new(Parser::new(lex(src), LangOptions::default())).collect();
+    assert!(matches!(
+        results.as_slice(),
+        [
+            Ok(Token::Ident(_)),
+            Err(ParseError::DivisionByZeroInCondition),
+            Ok(Token::Ident(_)),
+            Ok(Token::Eof)
+        ]
+    ));
+}
+
+#[test]
+fn passed_through_directives_can_be_stripped() {
+    let src = BStr::new("#define A 1\na # b\n#undef A\n");
+    let output_options = OutputOptions {
+        keep_defines: true,
+        ..OutputOptions::default()
+    };
+    let spellings = |strip| {
+        let parser = Parser::new(lex(src), LangOptions::default())
+            .with_output_options(output_options.clone());
+        Normalized::new(parser)
+            .with_passthrough_stripped(strip)
+            .map(|tok| tok.unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        spellings(true),
+        ["{ident 'a'}", "{punct .hash}", "{ident 'b'}", "{EOF}"]
+    );
+    // `# define A 1`, `a # b`, `# undef A` and the `Eof`
+    assert_eq!(spellings(false).len(), 11);
+}
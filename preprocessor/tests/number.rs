@@ -0,0 +1,102 @@
+use bstr::BStr;
+
+use preprocessor::number;
+use preprocessor::number::Base;
+use preprocessor::number::FloatError;
+use preprocessor::number::FloatType;
+use preprocessor::number::IntegerError;
+use preprocessor::number::IntegerType;
+use preprocessor::number::NumberKind;
+use preprocessor::target::Arch;
+use preprocessor::target::Os;
+use preprocessor::target::Target;
+
+fn integer(spelling: &str) -> Result<(u128, IntegerType), IntegerError> {
+    number::decode_integer(BStr::new(spelling), &Target::default())
+        .map(|integer| (integer.value, integer.ty))
+}
+
+fn float(spelling: &str) -> Result<(f64, FloatType), FloatError> {
+    number::decode_float(BStr::new(spelling)).map(|float| (float.value, float.ty))
+}
+
+#[test]
+fn integer_values_and_types() {
+    assert_eq!(integer("42"), Ok((42, IntegerType::Int)));
+    assert_eq!(integer("0x2a"), Ok((42, IntegerType::Int)));
+    assert_eq!(integer("052"), Ok((42, IntegerType::Int)));
+    assert_eq!(integer("0b101010"), Ok((42, IntegerType::Int)));
+    assert_eq!(integer("1'000'000"), Ok((1_000_000, IntegerType::Int)));
+    assert_eq!(integer("42u"), Ok((42, IntegerType::UnsignedInt)));
+    assert_eq!(integer("42LLU"), Ok((42, IntegerType::UnsignedLongLong)));
+    assert_eq!(integer("3wb"), Ok((3, IntegerType::BitInt(3))));
+    assert_eq!(integer("3uwb"), Ok((3, IntegerType::UnsignedBitInt(2))));
+}
+
+#[test]
+fn integer_types_grow_with_the_value() {
+    // a decimal constant stays signed, while octal and hex ones take the
+    // unsigned type of the same size first
+    assert_eq!(integer("2147483648"), Ok((1 << 31, IntegerType::Long)));
+    assert_eq!(
+        integer("0x80000000"),
+        Ok((1 << 31, IntegerType::UnsignedInt))
+    );
+    assert_eq!(
+        integer("0xffffffffffffffff"),
+        Ok((u64::MAX.into(), IntegerType::UnsignedLong))
+    );
+    assert_eq!(integer("18446744073709551616"), Err(IntegerError::TooLarge));
+
+    let windows = Target {
+        arch: Arch::X86_64,
+        os: Some(Os::Windows),
+        hosted: true,
+    };
+    let integer = number::decode_integer(BStr::new("2147483648"), &windows).unwrap();
+    assert_eq!(integer.ty, IntegerType::LongLong);
+}
+
+#[test]
+fn invalid_integers() {
+    assert_eq!(integer("08"), Err(IntegerError::InvalidDigit('8', "octal")));
+    assert_eq!(
+        integer("0b2"),
+        Err(IntegerError::InvalidDigit('2', "binary"))
+    );
+    assert_eq!(
+        integer("1lul"),
+        Err(IntegerError::InvalidSuffix("lul".into()))
+    );
+    assert_eq!(integer("1.5"), Err(IntegerError::NotInteger));
+}
+
+#[test]
+fn floats() {
+    assert_eq!(float("1.5"), Ok((1.5, FloatType::Double)));
+    assert_eq!(float(".5e1f"), Ok((5.0, FloatType::Float)));
+    assert_eq!(float("0x1.8p1L"), Ok((3.0, FloatType::LongDouble)));
+    assert_eq!(float("1.0dd"), Ok((1.0, FloatType::Decimal64)));
+    assert_eq!(float("1e"), Err(FloatError::EmptyExponent));
+    assert_eq!(float("0x1.8"), Err(FloatError::MissingBinaryExponent));
+    assert_eq!(float("1e99999f"), Err(FloatError::OutOfRange("float")));
+    assert_eq!(float("1.0q"), Err(FloatError::InvalidSuffix("q".into())));
+}
+
+#[test]
+fn split_numbers() {
+    let parts = number::split(BStr::new("0x1'f.8p-3fl"));
+    assert_eq!(parts.kind, NumberKind::Floating);
+    assert_eq!(parts.base, Base::Hex);
+    assert_eq!(parts.prefix, "0x");
+    assert_eq!(parts.digits, "1'f");
+    assert_eq!(parts.fraction, Some(BStr::new("8")));
+    assert_eq!(parts.exponent, Some(BStr::new("-3")));
+    assert_eq!(parts.suffix, "fl");
+
+    let parts = number::split(BStr::new("017ull"));
+    assert_eq!(parts.kind, NumberKind::Integer);
+    assert_eq!(parts.base, Base::Octal);
+    assert_eq!(parts.digits, "017");
+    assert_eq!(parts.suffix, "ull");
+}
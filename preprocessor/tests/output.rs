@@ -0,0 +1,115 @@
+use bstr::BStr;
+use bstr::BString;
+
+use preprocessor::definition::Macro;
+use preprocessor::lexer::lex;
+use preprocessor::lexer::lex_with_options;
+use preprocessor::lexer::lex_with_spacing;
+use preprocessor::lexer::Lexed;
+use preprocessor::options::CxxStandard;
+use preprocessor::options::LangOptions;
+use preprocessor::options::OutputOptions;
+use preprocessor::output::TextWriter;
+use preprocessor::parser::Parser;
+
+// write the tokens of `src` back out without their spacing, and check that
+// the text lexes to the same tokens.
+fn rewrite(src: &str, options: LangOptions) -> BString {
+    let mut writer = TextWriter::new(options.clone());
+    for (tok, _) in lex_with_spacing(BStr::new(src), options.clone()) {
+        writer.write(tok, false);
+    }
+    let text = writer.finish();
+    let before: Vec<_> = lex_with_options(BStr::new(src), options.clone()).collect();
+    let after: Vec<_> = lex_with_options(text.as_ref(), options).collect();
+    assert_eq!(before, after, "{text}");
+    text
+}
+
+#[test]
+fn separates_tokens_that_would_merge() {
+    let c = LangOptions::default;
+    assert_eq!(rewrite("a + b ;\n", c()), "a+b;\n");
+    assert_eq!(rewrite("a + + b - - c\n", c()), "a+ +b- -c\n");
+    assert_eq!(rewrite("int x\n", c()), "int x\n");
+    assert_eq!(rewrite("1 .e\n", c()), "1 .e\n");
+    assert_eq!(rewrite("x = \":\" ;\n", c()), "x=\":\";\n");
+    // not a token to the lexer, but a comment to anything reading the text
+    assert_eq!(rewrite("a / / b / * c\n", c()), "a/ /b/ *c\n");
+}
+
+#[test]
+fn looks_past_the_previous_token() {
+    let c = LangOptions::default;
+    assert_eq!(rewrite(". . .\n", c()), ".. .\n");
+    assert_eq!(rewrite("% : % :\n", c()), "% :% :\n");
+
+    let cxx = LangOptions {
+        cplusplus: Some(CxxStandard::Cxx20),
+        ..LangOptions::default()
+    };
+    // `<::>` would be the digraphs `<:` `:>`
+    assert_eq!(rewrite("a < :: >\n", cxx), "a<:: >\n");
+}
+
+#[test]
+fn keeps_the_spacing_the_parser_read() {
+    let src = "a + b; x - -y;\n#if 1\n  f (1) ;\n#endif\n";
+    let options = LangOptions::default();
+    let mut parser = Parser::new(
+        lex_with_spacing(BStr::new(src), options.clone()),
+        options.clone(),
+    );
+    let mut writer = TextWriter::new(options);
+    while let Some(tok) = parser.next() {
+        writer.write(tok.unwrap(), parser.space_before());
+    }
+    assert_eq!(writer.finish(), "a + b; x - -y;\n\nf (1) ;\n\n");
+}
+
+// the macros `src` defines, and its text with the definitions kept. the
+// parser is given the input's spacing, or just the tokens.
+fn kept_definitions(src: &str, spaced: bool) -> (Vec<(BString, Macro<'_>)>, BString) {
+    let options = LangOptions::default();
+    let keep = OutputOptions {
+        keep_defines: true,
+        ..OutputOptions::default()
+    };
+    let tokens = lex_with_spacing(BStr::new(src), options.clone())
+        .map(|(tok, space_before)| (tok, spaced && space_before));
+    let mut parser = Parser::new(tokens, options.clone())
+        .with_text(BStr::new(src))
+        .with_output_options(keep);
+    let mut writer = TextWriter::new(options);
+    while let Some(tok) = parser.next() {
+        writer.write(tok.unwrap(), parser.space_before());
+    }
+    (macros(&parser), writer.finish())
+}
+
+fn macros<'a, Tokens>(parser: &Parser<'a, Tokens>) -> Vec<(BString, Macro<'a>)>
+where
+    Tokens: Iterator,
+    Tokens::Item: Lexed<'a>,
+{
+    let mut macros: Vec<_> = parser
+        .macros()
+        .map(|(name, definition)| (name.to_owned(), definition.clone()))
+        .collect();
+    macros.sort_by(|a, b| a.0.cmp(&b.0));
+    macros
+}
+
+#[test]
+fn kept_definitions_read_back_the_same() {
+    let src = "#define F (x) x\n#define G(x) x ## 1\n#define H -1\n#define E\n#undef E\n";
+    for spaced in [true, false] {
+        let (before, text) = kept_definitions(src, spaced);
+        let mut parser =
+            Parser::new(lex(text.as_ref()), LangOptions::default()).with_text(text.as_ref());
+        parser.by_ref().for_each(drop);
+        assert_eq!(before, macros(&parser), "{text}");
+    }
+    let (_, text) = kept_definitions("#define F (x) x\n", false);
+    assert_eq!(text, "#define F (x)x\n");
+}
@@ -0,0 +1,154 @@
+use bstr::BStr;
+
+use preprocessor::callbacks::Callbacks;
+use preprocessor::lexer::lex;
+use preprocessor::options::LangOptions;
+use preprocessor::options::OutputOptions;
+use preprocessor::parser::ParseError;
+use preprocessor::parser::Parser;
+use preprocessor::pragma::OnOffSwitch;
+use preprocessor::pragma::Pragma;
+use preprocessor::pragma::RoundingDirection;
+use preprocessor::warning::WarningFlag;
+use preprocessor::warning::WarningOptions;
+
+#[test]
+fn stdc_pragmas_set_the_state() {
+    let src = "\
+#pragma STDC FP_CONTRACT OFF
+#pragma STDC FENV_ACCESS ON
+#pragma STDC FENV_ROUND FE_TOWARDZERO
+#pragma STDC FENV_DEC_ROUND FE_DEC_UPWARD
+";
+    let mut parser = Parser::new(lex(BStr::new(src)), LangOptions::default());
+    assert!(parser.by_ref().all(|result| result.is_ok()));
+    let stdc = parser.stdc();
+    assert_eq!(stdc.fp_contract, OnOffSwitch::Off);
+    assert_eq!(stdc.fenv_access, OnOffSwitch::On);
+    assert_eq!(stdc.cx_limited_range, OnOffSwitch::Default);
+    assert_eq!(stdc.fenv_round, RoundingDirection::TowardZero);
+    assert_eq!(stdc.fenv_dec_round, RoundingDirection::Upward);
+}
+
+fn error(src: &str) -> Option<ParseError> {
+    Parser::new(lex(BStr::new(src)), LangOptions::default()).find_map(Result::err)
+}
+
+#[test]
+fn invalid_stdc_operands_are_errors() {
+    assert!(matches!(
+        error("#pragma STDC FP_CONTRACT MAYBE\n"),
+        Some(ParseError::InvalidOnOffSwitch(_))
+    ));
+    // the decimal pragma takes the `FE_DEC_` names
+    assert!(matches!(
+        error("#pragma STDC FENV_DEC_ROUND FE_UPWARD\n"),
+        Some(ParseError::InvalidRoundingDirection(_))
+    ));
+}
+
+#[test]
+fn unknown_stdc_pragmas_are_warnings() {
+    let mut warnings = WarningOptions::default();
+    warnings.enable(WarningFlag::UnknownPragmas);
+    let src = BStr::new("#pragma STDC FROB ON\n");
+    let mut parser = Parser::new(lex(src), LangOptions::default()).with_warning_options(warnings);
+    assert!(parser.by_ref().all(|result| result.is_ok()));
+    let warnings = parser.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "PP1001");
+    assert!(
+        warnings[0].message.contains("`STDC FROB`"),
+        "{}",
+        warnings[0].message
+    );
+}
+
+#[test]
+fn region_pragmas_can_be_kept() {
+    let src = BStr::new("#pragma region a\nx\n#pragma endregion\n#pragma once\n");
+    let spellings = |keep_region_pragmas| {
+        let output_options = OutputOptions {
+            keep_region_pragmas,
+            ..OutputOptions::default()
+        };
+        Parser::new(lex(src), LangOptions::default())
+            .with_output_options(output_options)
+            .map(|tok| tok.unwrap().spelling().to_string())
+            .filter(|spelling| !spelling.is_empty())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        spellings(true),
+        [
+            "#",
+            "pragma",
+            "region",
+            "a",
+            "x",
+            "#",
+            "pragma",
+            "endregion"
+        ]
+    );
+    assert_eq!(spellings(false), ["x"]);
+}
+
+// claims `#pragma once`, as a client that implements it would.
+struct Once;
+
+impl Callbacks for Once {
+    fn pragma(&mut self, pragma: &Pragma<'_>) -> bool {
+        matches!(pragma, Pragma::Other(tokens) if tokens.first().is_some_and(|tok| tok.spelling() == "once"))
+    }
+}
+
+// the messages of the warnings from preprocessing `src`.
+fn warnings(src: &str, warnings: WarningOptions) -> Vec<String> {
+    let mut parser = Parser::new(lex(BStr::new(src)), LangOptions::default())
+        .with_warning_options(warnings)
+        .with_callbacks(Box::new(Once));
+    assert!(parser.by_ref().all(|result| result.is_ok()));
+    parser
+        .take_warnings()
+        .into_iter()
+        .map(|warning| warning.message)
+        .collect()
+}
+
+#[test]
+fn unknown_pragmas_are_reported_when_enabled() {
+    let src = "#pragma once\n#pragma weird thing\n#pragma region\n";
+    assert!(warnings(src, WarningOptions::default()).is_empty());
+    let mut enabled = WarningOptions::default();
+    enabled.enable(WarningFlag::UnknownPragmas);
+    assert_eq!(
+        warnings(src, enabled),
+        ["line 2: ignoring unknown pragma `weird`"]
+    );
+    // or by a pragma of its own
+    let src = "#pragma GCC diagnostic warning \"-Wunknown-pragmas\"\n#pragma weird\n";
+    assert_eq!(warnings(src, WarningOptions::default()).len(), 1);
+}
+
+#[test]
+fn pragma_messages() {
+    let src =
+        "#pragma message(\"building \" \"a\")\n#pragma message \"b\"\n#pragma message(c + 1)\n";
+    assert_eq!(
+        warnings(src, WarningOptions::default()),
+        ["line 1: building a", "line 2: b", "line 3: c + 1"]
+    );
+    let mut disabled = WarningOptions::default();
+    disabled.disable(WarningFlag::PragmaMessages);
+    assert!(warnings(src, disabled).is_empty());
+
+    assert!(matches!(
+        error("#pragma message\n"),
+        Some(ParseError::EmptyPragmaMessage)
+    ));
+    assert!(matches!(
+        error("#pragma message(\"a\"\n"),
+        Some(ParseError::UnterminatedPragmaMessage)
+    ));
+}
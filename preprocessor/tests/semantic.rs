@@ -0,0 +1,54 @@
+use bstr::BStr;
+
+use preprocessor::options::LangOptions;
+use preprocessor::semantic;
+use preprocessor::semantic::SemanticClass;
+
+// each classified span as (line, column, text, class).
+fn classify(src: &str) -> Vec<(usize, usize, &str, SemanticClass)> {
+    let lines: Vec<_> = src.lines().collect();
+    semantic::classify(BStr::new(src), &LangOptions::default())
+        .into_iter()
+        .map(|tok| {
+            let text = &lines[tok.line][tok.column..tok.column + tok.len];
+            (tok.line, tok.column, text, tok.class)
+        })
+        .collect()
+}
+
+#[test]
+fn classes() {
+    use SemanticClass::*;
+    let src = "\
+#include <a.h>
+#define MAX(a, b) ((a) > (b) ? a : b) // bigger
+int x = MAX(1, 0x2);
+char *s = \"str\"; /* one
+two */
+";
+    assert_eq!(
+        classify(src),
+        [
+            // directive names count as keywords
+            (0, 1, "include", Keyword),
+            (0, 9, "<a.h>", HeaderName),
+            (1, 1, "define", Keyword),
+            (1, 8, "MAX", MacroName),
+            (1, 12, "a", MacroParameter),
+            (1, 15, "b", MacroParameter),
+            (1, 20, "a", MacroParameter),
+            (1, 26, "b", MacroParameter),
+            (1, 31, "a", MacroParameter),
+            (1, 35, "b", MacroParameter),
+            (1, 38, "// bigger", Comment),
+            (2, 0, "int", Keyword),
+            (2, 8, "MAX", MacroName),
+            (2, 12, "1", Number),
+            (2, 15, "0x2", Number),
+            (3, 0, "char", Keyword),
+            (3, 10, "\"str\"", String),
+            (3, 17, "/* one", Comment),
+            (4, 0, "two */", Comment),
+        ]
+    );
+}
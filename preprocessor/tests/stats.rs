@@ -0,0 +1,27 @@
+use bstr::BStr;
+
+use preprocessor::lexer::lex;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::Parser;
+
+#[test]
+fn counts_directives_and_their_expansions() {
+    let src = "\
+#define A B
+#define B 1
+#include \"missing.h\"
+#if A
+#line B
+#endif
+A
+";
+    let mut parser = Parser::new(lex(BStr::new(src)), LangOptions::default());
+    parser.by_ref().for_each(drop);
+    let stats = parser.stats();
+    assert_eq!(stats.include_directives, 1);
+    // the text line isn't expanded
+    assert_eq!(
+        stats.directive_expansions_by_count(),
+        [(BStr::new("B"), 2), (BStr::new("A"), 1)]
+    );
+}
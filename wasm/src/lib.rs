@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice;
+use preprocessor::diagnostic::Diagnostic;
+use preprocessor::files::FileProvider;
+use preprocessor::lexer::lex_with_options;
+use preprocessor::options::CxxStandard;
+use preprocessor::options::LangOptions;
+use preprocessor::parser::Parser;
+use preprocessor::token::Token;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Preprocessor {
+    options: LangOptions,
+    files: Files,
+}
+
+// the headers `addFile` was given, by path. there is no filesystem to search,
+// so `"a.h"` is looked for next to the including file and then as `a.h`, and
+// `<a.h>` only as `a.h`.
+#[derive(Debug, Clone, Default)]
+struct Files(HashMap<BString, Vec<u8>>);
+
+impl FileProvider for Files {
+    fn resolve(&self, name: &BStr, quoted: bool, from: &BStr) -> Option<BString> {
+        let dir = from.rfind_byte(b'/').map(|end| &from[..=end]);
+        let local = dir
+            .filter(|_| quoted)
+            .map(|dir| [dir.as_bytes(), name.as_bytes()].concat());
+        local
+            .into_iter()
+            .chain([name.to_vec()])
+            .map(BString::from)
+            .find(|path| self.0.contains_key(path))
+    }
+
+    fn read(&self, path: &BStr) -> Option<Vec<u8>> {
+        self.0.get(path).cloned()
+    }
+}
+
+// the result of one `preprocess` call, as parallel arrays.
+#[wasm_bindgen]
+pub struct Output {
+    kinds: Vec<String>,
+    spellings: Vec<String>,
+    diagnostics: Vec<String>,
+    includes: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl Preprocessor {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[wasm_bindgen(js_name = setGnuExtensions)]
+    pub fn set_gnu_extensions(&mut self, enabled: bool) {
+        self.options.gnu_extensions = enabled;
+    }
+
+    // 0 selects C. returns false for an unknown `__cplusplus` value.
+    #[wasm_bindgen(js_name = setCplusplus)]
+    pub fn set_cplusplus(&mut self, version: i64) -> bool {
+        if version == 0 {
+            self.options.cplusplus = None;
+            return true;
+        }
+        let std = CxxStandard::from_version(version);
+        if std.is_some() {
+            self.options.cplusplus = std;
+        }
+        std.is_some()
+    }
+
+    // make `path` available to `#include`.
+    #[wasm_bindgen(js_name = addFile)]
+    pub fn add_file(&mut self, path: &str, contents: &str) {
+        self.files.0.insert(path.into(), contents.into());
+    }
+
+    pub fn preprocess(&self, source: &str) -> Output {
+        let text = initial::fused::process(BStr::new(source));
+        let tokens = lex_with_options(text.as_ref(), self.options.clone());
+        let mut parser = Parser::new(tokens, self.options.clone())
+            .with_text(text.as_ref())
+            .with_file_provider(Box::new(self.files.clone()));
+        let mut output = Output {
+            kinds: vec![],
+            spellings: vec![],
+            diagnostics: vec![],
+            includes: vec![],
+        };
+        while let Some(result) = parser.next() {
+            match result {
+                Ok(tok) => {
+                    output.kinds.push(kind(tok).to_owned());
+                    output.spellings.push(tok.spelling().to_string());
+                    if let Token::Eof = tok {
+                        break;
+                    }
+                }
+                Err(e) => output.diagnostics.push(Diagnostic::from(&e).to_string()),
+            }
+            for warning in parser.take_warnings() {
+                output.diagnostics.push(warning.to_string());
+            }
+        }
+        let graph = parser.include_graph();
+        for edge in &graph.edges {
+            output.includes.push(graph.files()[edge.to].to_string());
+        }
+        output
+    }
+}
+
+#[wasm_bindgen]
+impl Output {
+    #[wasm_bindgen(getter)]
+    pub fn kinds(&self) -> Vec<String> {
+        self.kinds.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn spellings(&self) -> Vec<String> {
+        self.spellings.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn diagnostics(&self) -> Vec<String> {
+        self.diagnostics.clone()
+    }
+
+    // each header included, by the path `addFile` gave it if it was found,
+    // or else as written.
+    #[wasm_bindgen(getter)]
+    pub fn includes(&self) -> Vec<String> {
+        self.includes.clone()
+    }
+}
+
+fn kind(tok: Token<'_>) -> &'static str {
+    match tok {
+        Token::Ident(_) => "ident",
+        Token::StringLit(_) => "string",
+        Token::Number(_) => "number",
+        Token::Punct(_) => "punct",
+        Token::ModuleKeyword(_) => "module-keyword",
+        Token::Keyword(_) => "keyword",
+        Token::Other(_) => "other",
+        Token::Eol => "eol",
+        Token::Eof => "eof",
+    }
+}
@@ -0,0 +1,32 @@
+use wasm::Preprocessor;
+
+#[test]
+fn tokens_and_diagnostics() {
+    let output = Preprocessor::new().preprocess("#if 1 / 0\n#endif\na+1\n");
+    assert_eq!(
+        output.kinds(),
+        ["eol", "eol", "ident", "punct", "number", "eol", "eof"]
+    );
+    assert_eq!(output.spellings()[2..5], ["a", "+", "1"]);
+    assert_eq!(output.diagnostics().len(), 1);
+    assert!(output.diagnostics()[0].contains("PP0012"));
+}
+
+#[test]
+fn options() {
+    let mut preprocessor = Preprocessor::new();
+    assert!(!preprocessor.set_cplusplus(199000));
+    assert!(preprocessor.set_cplusplus(202002));
+    let output = preprocessor.preprocess("#if __cplusplus == 202002L\nyes\n#endif\n");
+    assert!(output.spellings().contains(&"yes".to_owned()));
+}
+
+#[test]
+fn includes_are_found_among_the_added_files() {
+    let mut preprocessor = Preprocessor::new();
+    preprocessor.add_file("a.h", "");
+    preprocessor.add_file("dir/b.h", "");
+    let src = "#include \"a.h\"\n#include <b.h>\n#line 1 \"dir/main.c\"\n#include \"b.h\"\n";
+    let output = preprocessor.preprocess(src);
+    assert_eq!(output.includes(), ["a.h", "b.h", "dir/b.h"]);
+}